@@ -0,0 +1,58 @@
+// benches/insert.rs
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rsql::{execute_insert, Row, Table, MAX_ROWS, ROWS_PER_PAGE};
+
+const TABLE_SIZES: [usize; 3] = [ROWS_PER_PAGE, 10 * ROWS_PER_PAGE, MAX_ROWS];
+
+fn rows(count: usize) -> Vec<Row> {
+    (0..count as u64)
+        .map(|id| Row::new(id, format!("user{}", id), format!("user{}@example.com", id)))
+        .collect()
+}
+
+/// An empty table whose first `count` rows' pages are already allocated.
+fn warm_table(rows: &[Row]) -> Table {
+    let mut table = Table::new();
+    for row in rows {
+        execute_insert(&mut table, row);
+    }
+    table.num_rows = 0;
+    table
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for size in TABLE_SIZES {
+        let rows = rows(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("cold", size), &rows, |b, rows| {
+            b.iter_batched(
+                Table::new,
+                |mut table| {
+                    for row in rows {
+                        execute_insert(&mut table, row);
+                    }
+                    table
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("warm", size), &rows, |b, rows| {
+            b.iter_batched(
+                || warm_table(rows),
+                |mut table| {
+                    for row in rows {
+                        execute_insert(&mut table, row);
+                    }
+                    table
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert);
+criterion_main!(benches);
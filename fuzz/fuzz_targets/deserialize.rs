@@ -0,0 +1,12 @@
+// fuzz/fuzz_targets/deserialize.rs
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rsql::{try_deserialize, ROW_SIZE};
+
+fuzz_target!(|data: &[u8]| {
+    match try_deserialize(data) {
+        Ok(row) => assert_eq!(&row.to_bytes()[..], data),
+        Err(_) => assert_ne!(data.len(), ROW_SIZE),
+    }
+});
@@ -0,0 +1,128 @@
+// src/actor.rs
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use crate::{deserialize, execute_insert, ExecuteResult, Row, Table};
+
+enum Command {
+    Insert(Box<Row>, ReplySender<ExecuteResult>),
+    Select(ReplySender<Vec<Row>>),
+    Count(ReplySender<usize>),
+}
+
+/// Owns a `Table` on a dedicated thread and serves async callers through a
+/// command queue, so the table's `&mut self` methods only ever run on that
+/// one thread. Clones share the same table; the thread exits once every
+/// handle has been dropped and the queue has drained.
+#[derive(Clone, Debug)]
+pub struct TableActor {
+    sender: mpsc::Sender<Command>,
+}
+
+impl TableActor {
+    pub fn spawn(table: Table) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || run(table, receiver));
+        TableActor { sender }
+    }
+
+    pub async fn insert(&self, row: Row) -> ExecuteResult {
+        self.request(|reply| Command::Insert(Box::new(row), reply)).await
+    }
+
+    pub async fn select(&self) -> Vec<Row> {
+        self.request(Command::Select).await
+    }
+
+    pub async fn count(&self) -> usize {
+        self.request(Command::Count).await
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(ReplySender<T>) -> Command) -> T {
+        let (reply, receiver) = reply_channel();
+        self.sender
+            .send(command(reply))
+            .expect("table actor stopped");
+        receiver.await.expect("table actor stopped")
+    }
+}
+
+fn run(mut table: Table, receiver: mpsc::Receiver<Command>) {
+    for command in receiver {
+        match command {
+            Command::Insert(row, reply) => reply.send(execute_insert(&mut table, &row)),
+            Command::Select(reply) => {
+                let rows = table
+                    .live_indices()
+                    .map(|i| deserialize(&table.read_slot(i)))
+                    .collect();
+                reply.send(rows)
+            }
+            Command::Count(reply) => reply.send(table.live_rows()),
+        }
+    }
+}
+
+struct ReplySlot<T> {
+    value: Option<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+/// The actor's half of a single-use reply channel. Dropping it unsent
+/// resolves the receiver to `None`.
+struct ReplySender<T> {
+    slot: Arc<Mutex<ReplySlot<T>>>,
+}
+
+struct ReplyReceiver<T> {
+    slot: Arc<Mutex<ReplySlot<T>>>,
+}
+
+fn reply_channel<T>() -> (ReplySender<T>, ReplyReceiver<T>) {
+    let slot = Arc::new(Mutex::new(ReplySlot {
+        value: None,
+        closed: false,
+        waker: None,
+    }));
+    (ReplySender { slot: Arc::clone(&slot) }, ReplyReceiver { slot })
+}
+
+impl<T> ReplySender<T> {
+    fn send(self, value: T) {
+        self.slot.lock().unwrap().value = Some(value);
+        // waking happens in drop
+    }
+}
+
+impl<T> Drop for ReplySender<T> {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock().unwrap();
+        slot.closed = true;
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for ReplyReceiver<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut slot = self.slot.lock().unwrap();
+        if let Some(value) = slot.value.take() {
+            return Poll::Ready(Some(value));
+        }
+        if slot.closed {
+            return Poll::Ready(None);
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
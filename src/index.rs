@@ -0,0 +1,67 @@
+// src/index.rs
+
+use std::{collections::HashMap, sync::MutexGuard};
+
+use crate::{deserialize, DbError, Row, Table};
+
+/// Row indices keyed by username. Usernames are not unique, so each maps
+/// to every row holding it, in ascending order. NULL usernames are left out.
+pub(crate) type UsernameIndex = HashMap<String, Vec<usize>>;
+
+impl Table {
+    fn lock_username_index(&self) -> MutexGuard<'_, Option<UsernameIndex>> {
+        self.username_index.lock().expect("username index lock poisoned")
+    }
+
+    /// Indices of the rows whose username is `username`, in row order.
+    /// The index is built on first use and kept up to date by inserts;
+    /// other writes drop it to be rebuilt by the next lookup. Panics if a
+    /// page fails to load; see `try_find_username`.
+    pub fn find_username(&self, username: &str) -> Vec<usize> {
+        self.try_find_username(username).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `find_username`, but a page that fails to load while the index
+    /// is built is a `Read` error instead of a panic.
+    pub fn try_find_username(&self, username: &str) -> Result<Vec<usize>, DbError> {
+        let mut index = self.lock_username_index();
+        if index.is_none() {
+            let mut built = UsernameIndex::new();
+            for i in self.try_live_indices() {
+                let i = i?;
+                if let Some(username) = deserialize(&self.try_read_slot(i)?).username_str() {
+                    built.entry(username.into_owned()).or_default().push(i);
+                }
+            }
+            *index = Some(built);
+        }
+        let rows = index.as_ref().and_then(|index| index.get(username)).map_or(&[][..], Vec::as_slice);
+        Ok(rows.iter().copied().filter(|&i| i < self.num_rows).collect())
+    }
+
+    /// Records a row just stored at `position` by an insert.
+    pub(crate) fn index_insert(&mut self, position: usize, row: &Row) {
+        let index = self.username_index.get_mut().expect("username index lock poisoned");
+        if let (Some(index), Some(username)) = (index, row.username_str()) {
+            index.entry(username.into_owned()).or_default().push(position);
+        }
+    }
+
+    /// Approximate heap bytes held by the index, or 0 while it is unbuilt.
+    pub(crate) fn index_memory_usage(&self) -> usize {
+        self.lock_username_index().as_ref().map_or(0, |index| {
+            index
+                .iter()
+                .map(|(username, rows)| {
+                    std::mem::size_of::<(String, Vec<usize>)>()
+                        + username.capacity()
+                        + rows.capacity() * std::mem::size_of::<usize>()
+                })
+                .sum()
+        })
+    }
+
+    pub(crate) fn invalidate_index(&mut self) {
+        *self.username_index.get_mut().expect("username index lock poisoned") = None;
+    }
+}
@@ -0,0 +1,143 @@
+// src/jsonl.rs
+
+use std::{iter::Peekable, str::Chars};
+
+/// A value in one of the flat objects `import_jsonl` reads. Numbers keep
+/// their text so the caller decides what range it accepts.
+#[derive(Debug, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Number(String),
+    String(String),
+}
+
+/// Parses a single JSON object whose values are strings, numbers or null,
+/// returning its members in order. Nested objects, arrays and booleans are
+/// rejected, as is anything after the closing brace.
+pub(crate) fn parse_object(line: &str) -> Result<Vec<(String, JsonValue)>, String> {
+    let mut chars = line.chars().peekable();
+    let mut members = Vec::new();
+    expect(&mut chars, '{')?;
+    if skip_whitespace(&mut chars) == Some('}') {
+        chars.next();
+    } else {
+        loop {
+            expect(&mut chars, '"')?;
+            let key = parse_string(&mut chars)?;
+            expect(&mut chars, ':')?;
+            let value = parse_value(&mut chars)?;
+            members.push((key, value));
+            match skip_whitespace(&mut chars) {
+                Some(',') => {
+                    chars.next();
+                }
+                Some('}') => {
+                    chars.next();
+                    break;
+                }
+                Some(c) => return Err(format!("expected ',' or '}}', found '{}'", c)),
+                None => return Err("unterminated object".to_string()),
+            }
+        }
+    }
+    match skip_whitespace(&mut chars) {
+        None => Ok(members),
+        Some(c) => Err(format!("unexpected '{}' after object", c)),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) -> Option<char> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    chars.peek().copied()
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    match skip_whitespace(chars) {
+        Some(c) if c == expected => {
+            chars.next();
+            Ok(())
+        }
+        Some(c) => Err(format!("expected '{}', found '{}'", expected, c)),
+        None => Err(format!("expected '{}', found end of line", expected)),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<JsonValue, String> {
+    match skip_whitespace(chars) {
+        Some('"') => {
+            chars.next();
+            parse_string(chars).map(JsonValue::String)
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| matches!(c, '-' | '+' | '.' | 'e' | 'E') || c.is_ascii_digit()) {
+                number.push(c);
+            }
+            Ok(JsonValue::Number(number))
+        }
+        Some(c) if c.is_ascii_alphabetic() => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Ok(JsonValue::Null),
+                _ => Err(format!("unsupported value '{}'", word)),
+            }
+        }
+        Some(c) => Err(format!("unsupported value starting with '{}'", c)),
+        None => Err("expected a value, found end of line".to_string()),
+    }
+}
+
+/// Reads the rest of a string whose opening quote has been consumed,
+/// undoing the escapes `json_string` writes along with the rest of JSON's.
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(text),
+            Some('\\') => match chars.next() {
+                Some('"') => text.push('"'),
+                Some('\\') => text.push('\\'),
+                Some('/') => text.push('/'),
+                Some('b') => text.push('\u{8}'),
+                Some('f') => text.push('\u{c}'),
+                Some('n') => text.push('\n'),
+                Some('r') => text.push('\r'),
+                Some('t') => text.push('\t'),
+                Some('u') => text.push(parse_unicode_escape(chars)?),
+                Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+                None => return Err("unterminated string".to_string()),
+            },
+            Some(c) => text.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+/// The character for a `\uXXXX` escape, joining a surrogate pair when the
+/// first half is followed by a second `\uXXXX`.
+fn parse_unicode_escape(chars: &mut Peekable<Chars>) -> Result<char, String> {
+    let high = hex4(chars)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return char::from_u32(high).ok_or_else(|| format!("invalid escape '\\u{:04x}'", high));
+    }
+    if chars.next() != Some('\\') || chars.next() != Some('u') {
+        return Err(format!("unpaired surrogate '\\u{:04x}'", high));
+    }
+    let low = hex4(chars)?;
+    if !(0xDC00..0xE000).contains(&low) {
+        return Err(format!("unpaired surrogate '\\u{:04x}'", high));
+    }
+    let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+    char::from_u32(code).ok_or_else(|| format!("invalid escape '\\u{:04x}'", high))
+}
+
+fn hex4(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let digits: String = chars.take(4).collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid escape '\\u{}'", digits));
+    }
+    Ok(u32::from_str_radix(&digits, 16).unwrap())
+}
@@ -1,33 +1,74 @@
 // src/lib.rs
 
-use std::{fmt::write, io::{self, Write}, ptr, str::from_utf8};
+use std::{borrow::Cow, collections::{HashMap, HashSet}, error::Error, fmt, fs::File, io::{self, BufRead, Write}, path::Path, ptr, str::from_utf8, sync::Mutex, time::Instant};
 
-pub const COLUMN_ID_SIZE: usize = 4;
+mod actor;
+mod index;
+mod jsonl;
+mod pager;
+#[cfg(feature = "serde")]
+mod row_serde;
+mod shared;
+mod tokenizer;
+mod wal;
+
+pub use actor::TableActor;
+use index::UsernameIndex;
+use jsonl::JsonValue;
+use pager::PageCache;
+use wal::WalMarker;
+pub use pager::{crc32, page_offset, FileHeader, OpenError, ReadError, TruncatedPage, FILE_MAGIC, FORMAT_VERSION, HEADER_SIZE};
+pub use shared::SharedTable;
+pub use tokenizer::{tokenize, Token, TokenizeError};
+pub use wal::{wal_path, WAL_RECORD_SIZE};
+
+pub const COLUMN_ID_SIZE: usize = 8;
 pub const COLUMN_USERNAME_SIZE: usize = 32;
 pub const COLUMN_EMAIL_SIZE: usize = 255;
 pub const COLUMN_ID_OFFSET: usize = 0;
 pub const COLUMN_USERNAME_OFFSET: usize = COLUMN_ID_SIZE;
 pub const COLUMN_EMAIL_OFFSET: usize = COLUMN_USERNAME_OFFSET + COLUMN_USERNAME_SIZE;
-pub const ROW_SIZE: usize = COLUMN_ID_SIZE + COLUMN_USERNAME_SIZE + COLUMN_EMAIL_SIZE;
+pub const COLUMN_NULLS_SIZE: usize = 1;
+pub const COLUMN_NULLS_OFFSET: usize = COLUMN_EMAIL_OFFSET + COLUMN_EMAIL_SIZE;
+pub const ROW_SIZE: usize = COLUMN_ID_SIZE + COLUMN_USERNAME_SIZE + COLUMN_EMAIL_SIZE + COLUMN_NULLS_SIZE;
+// bits of `Row::nulls`, set when the column holds NULL rather than a string
+pub const NULL_USERNAME: u8 = 1 << 0;
+pub const NULL_EMAIL: u8 = 1 << 1;
+// set in the same byte on a row removed by `delete where`; `vacuum` drops it
+pub const ROW_DELETED: u8 = 1 << 2;
 pub const PAGE_SIZE: usize = 4096;
 pub const MAX_PAGES: usize = 100;
-pub const ROWS_PER_PAGE: usize = PAGE_SIZE / ROW_SIZE;
+pub const PAGE_CHECKSUM_SIZE: usize = 4;
+pub const ROWS_PER_PAGE: usize = (PAGE_SIZE - PAGE_CHECKSUM_SIZE) / ROW_SIZE;
 pub const MAX_ROWS: usize = MAX_PAGES * ROWS_PER_PAGE;
+// select flushes its writer after this many rows so output reaches slow
+// consumers promptly instead of piling up in a buffered writer.
+pub const SELECT_FLUSH_INTERVAL: usize = 64;
+// screenful `.pager on` pauses after when no line count is given
+pub const DEFAULT_PAGER_LINES: usize = 24;
 
-#[derive(Debug, PartialEq)]
+// ordering follows field order: id, then username, then email
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "row_serde::RowRepr", try_from = "row_serde::RowRepr")
+)]
 #[repr(C)]
 pub struct Row {
-    pub id: u32,
+    pub id: u64,
     pub username: [u8; COLUMN_USERNAME_SIZE],
     pub email: [u8; COLUMN_EMAIL_SIZE],
+    pub nulls: u8,
 }
 
 impl Row {
-    pub fn new(id: u32, username: String, email: String) -> Row {
+    pub fn new(id: u64, username: String, email: String) -> Row {
         let mut row = Row {
             id,
             username: [0; COLUMN_USERNAME_SIZE],
             email: [0; COLUMN_EMAIL_SIZE],
+            nulls: 0,
         };
         let username_bytes = username.as_bytes();
         let email_bytes = email.as_bytes();
@@ -36,62 +77,689 @@ impl Row {
         row
     }
 
+    pub fn try_new(id: u64, username: String, email: String) -> Result<Row, DbError> {
+        if username.len() > COLUMN_USERNAME_SIZE || email.len() > COLUMN_EMAIL_SIZE {
+            return Err(DbError::StringTooLong);
+        }
+        Ok(Row::new(id, username, email))
+    }
+
+    /// Like `try_new`, but `None` stores a NULL column.
+    pub fn try_new_nullable(id: u64, username: Option<String>, email: Option<String>) -> Result<Row, DbError> {
+        let mut nulls = 0;
+        if username.is_none() {
+            nulls |= NULL_USERNAME;
+        }
+        if email.is_none() {
+            nulls |= NULL_EMAIL;
+        }
+        let mut row = Row::try_new(id, username.unwrap_or_default(), email.unwrap_or_default())?;
+        row.nulls = nulls;
+        Ok(row)
+    }
+
+    /// The row's on-page encoding, as written by `serialize`.
+    pub fn to_bytes(&self) -> [u8; ROW_SIZE] {
+        let mut bytes = [0; ROW_SIZE];
+        serialize(self, &mut bytes);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; ROW_SIZE]) -> Row {
+        deserialize(bytes)
+    }
+
     pub fn write<W: Write>(&self, writer: &mut W) {
-        let username = from_utf8(&self.username).unwrap_or("Invalid UTF-8");
-        let email = from_utf8(&self.email).unwrap_or("Invalid UTF-8");
-        writeln!(
-            writer,
-            "{} {} {}",
-            self.id,
-            username.trim_end_matches('\0'),
-            email.trim_end_matches('\0')
-        ).unwrap();
-    }
-    pub fn to_string(&self) -> String{
-        let username = from_utf8(&self.username).unwrap_or("Invalid UTF-8");
-        let email = from_utf8(&self.email).unwrap_or("Invalid UTF-8");
-        format!(
-            "{} {} {}",
-            self.id,
-            username.trim_end_matches('\0'),
-            email.trim_end_matches('\0')
-        )
+        writeln!(writer, "{}", self).unwrap();
+    }
+    pub fn write_columns<W: Write>(&self, columns: &[Column], separator: &str, writer: &mut W) {
+        let fields: Vec<Cow<str>> = columns
+            .iter()
+            .map(|&column| display_nullable(self.value(column)))
+            .collect();
+        writeln!(writer, "{}", fields.join(separator)).unwrap();
+    }
+
+    /// Like `write_columns`, but with the username and email written as
+    /// every stored byte, NUL padding included, for `.raw`.
+    pub fn write_raw_columns<W: Write>(&self, columns: &[Column], separator: &str, writer: &mut W) {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|&column| match column {
+                Column::Id => self.id.to_string(),
+                Column::Username => escape_bytes(&self.username),
+                Column::Email => escape_bytes(&self.email),
+            })
+            .collect();
+        writeln!(writer, "{}", fields.join(separator)).unwrap();
+    }
+
+    fn value(&self, column: Column) -> Option<Cow<'_, str>> {
+        match column {
+            Column::Id => Some(Cow::Owned(self.id.to_string())),
+            Column::Username => self.username_str(),
+            Column::Email => self.email_str(),
+        }
+    }
+
+    fn csv_line(&self, columns: &[Column]) -> String {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|&column| csv_field(&self.value(column).unwrap_or_default()))
+            .collect();
+        fields.join(",")
+    }
+
+    fn json_object(&self, columns: &[Column]) -> String {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|&column| {
+                let value = match (column, self.value(column)) {
+                    (Column::Id, _) => self.id.to_string(),
+                    (_, Some(value)) => json_string(&value),
+                    (_, None) => "null".to_string(),
+                };
+                format!("\"{}\":{}", column.name(), value)
+            })
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.nulls & ROW_DELETED != 0
+    }
+
+    /// The username without its NUL padding, or `None` if it is NULL;
+    /// matches `to_string` for bytes that are not valid UTF-8.
+    pub fn username_str(&self) -> Option<Cow<'_, str>> {
+        (self.nulls & NULL_USERNAME == 0).then(|| Cow::Borrowed(column_str(&self.username)))
+    }
+
+    /// The email without its NUL padding, or `None` if it is NULL; matches
+    /// `to_string` for bytes that are not valid UTF-8.
+    pub fn email_str(&self) -> Option<Cow<'_, str>> {
+        (self.nulls & NULL_EMAIL == 0).then(|| Cow::Borrowed(column_str(&self.email)))
+    }
+}
+
+/// `id username email`, with NUL padding trimmed and NULL columns shown as
+/// `NULL`.
+impl fmt::Display for Row {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.id, display_nullable(self.username_str()), display_nullable(self.email_str()))
+    }
+}
+
+fn column_str(bytes: &[u8]) -> &str {
+    from_utf8(bytes).unwrap_or("Invalid UTF-8").trim_end_matches('\0')
+}
+
+fn display_nullable(value: Option<Cow<'_, str>>) -> Cow<'_, str> {
+    value.unwrap_or(Cow::Borrowed("NULL"))
+}
+
+/// Visible ASCII as itself and every other byte, spaces included, as
+/// `\xNN`, with `\` doubled; the result never contains a space, so fields
+/// joined by spaces stay apart.
+fn escape_bytes(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        match byte {
+            b'\\' => escaped.push_str("\\\\"),
+            b'!'..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Undo information for an open transaction: the row count at `begin` and
+/// the original contents of each page first written after it.
+#[derive(Clone, Debug)]
+struct Transaction {
+    num_rows: usize,
+    next_id: u64,
+    saved_pages: HashMap<usize, Option<Box<[u8]>>>,
+}
+
+/// The most bytes each string column stores, from `Table::column_limits`.
+/// Longer values are refused with `StringTooLong`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnLimits {
+    pub username: usize,
+    pub email: usize,
+}
+
+/// Page geometry for a table. The defaults are `PAGE_SIZE` and `MAX_PAGES`;
+/// smaller pages are mainly useful for exercising multi-page logic in tests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableConfig {
+    pub page_size: usize,
+    pub max_pages: usize,
+    /// Most pages kept in memory at once for file-backed tables; the least
+    /// recently used page is written back and dropped to make room.
+    pub cache_capacity: usize,
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        TableConfig {
+            page_size: PAGE_SIZE,
+            max_pages: MAX_PAGES,
+            cache_capacity: MAX_PAGES,
+        }
+    }
+}
+
+impl TableConfig {
+    pub fn rows_per_page(&self) -> usize {
+        (self.page_size - PAGE_CHECKSUM_SIZE) / ROW_SIZE
+    }
+
+    pub fn max_rows(&self) -> usize {
+        self.max_pages * self.rows_per_page()
     }
 }
 
 #[derive(Debug)]
 pub struct Table {
     pub num_rows: usize,
-    pages: [Option<Box<[u8; PAGE_SIZE]>>; MAX_PAGES],
+    /// The id `insert <username> <email>` assigns next; always past every
+    /// id inserted so far.
+    pub next_id: u64,
+    config: TableConfig,
+    // behind a mutex so that reads through `&Table` can load pages on demand
+    cache: Mutex<PageCache>,
+    file: Option<File>,
+    wal: Option<File>,
+    transaction: Option<Transaction>,
+    username_index: Mutex<Option<UsernameIndex>>,
+    // set by `set_max_rows` to bound the table below what its pages can hold
+    row_cap: Option<usize>,
+    // set by `open_readonly`; writes are refused and nothing is flushed
+    read_only: bool,
+    // set by `open` when the file's last page was cut short
+    truncated: Option<TruncatedPage>,
+}
+
+/// Snapshots the rows, dirty flags and any open transaction. The clone is
+/// in-memory only: it does not share the original's file or write-ahead log,
+/// so any pages not yet loaded from the file are loaded into the clone.
+impl Clone for Table {
+    fn clone(&self) -> Self {
+        let mut cache = self.lock_cache().clone();
+        cache.capacity = cache.pages.len();
+        for page_num in 0..cache.pages.len() {
+            self.load_page_or_panic(&mut cache, page_num);
+        }
+        cache.on_disk.iter_mut().for_each(|on_disk| *on_disk = false);
+        Self {
+            num_rows: self.num_rows,
+            next_id: self.next_id,
+            config: self.config,
+            cache: Mutex::new(cache),
+            file: None,
+            wal: None,
+            transaction: self.transaction.clone(),
+            username_index: Mutex::new(None),
+            row_cap: self.row_cap,
+            read_only: self.read_only,
+            truncated: self.truncated.clone(),
+        }
+    }
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Table {
     pub fn new() -> Self {
+        Self::with_config(TableConfig::default())
+    }
+
+    /// Panics if a page is too small to hold a single row, or the cache
+    /// cannot hold a page.
+    pub fn with_config(config: TableConfig) -> Self {
+        assert!(
+            config.rows_per_page() > 0,
+            "page size {} cannot hold a {}-byte row",
+            config.page_size,
+            ROW_SIZE
+        );
+        assert!(config.cache_capacity > 0, "page cache capacity must be at least 1");
         Self {
             num_rows: 0,
-            pages: [(); MAX_PAGES].map(|_| None),
+            next_id: 1,
+            config,
+            cache: Mutex::new(PageCache::new(config.max_pages, config.cache_capacity)),
+            file: None,
+            wal: None,
+            transaction: None,
+            username_index: Mutex::new(None),
+            row_cap: None,
+            read_only: false,
+            truncated: None,
         }
     }
 
-    pub fn row_slot(&mut self, index: usize) -> &mut [u8] {
-        let page_num = index / ROWS_PER_PAGE;
-        if page_num >= MAX_PAGES {
+    pub fn config(&self) -> TableConfig {
+        self.config
+    }
+
+    /// The byte capacities of the string columns, for callers that check
+    /// input before inserting it.
+    pub const fn column_limits() -> ColumnLimits {
+        ColumnLimits { username: COLUMN_USERNAME_SIZE, email: COLUMN_EMAIL_SIZE }
+    }
+
+    /// The most rows the table accepts: what its pages can hold, lowered by
+    /// any cap from `set_max_rows`.
+    pub fn max_rows(&self) -> usize {
+        match self.row_cap {
+            Some(cap) => cap.min(self.config.max_rows()),
+            None => self.config.max_rows(),
+        }
+    }
+
+    /// Caps the number of rows at `max_rows`, or lifts the cap with `None`.
+    /// Inserts past the cap fail with `TableFull`; rows already stored are
+    /// kept.
+    pub fn set_max_rows(&mut self, max_rows: Option<usize>) {
+        self.row_cap = max_rows;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Flushes the table so its file holds exactly the committed rows, then
+    /// logs the start of a transaction. Until it ends, nothing it changes is
+    /// written to the file; see `flush_dirty`.
+    pub fn begin_transaction(&mut self) -> io::Result<()> {
+        self.flush_dirty()?;
+        self.wal_mark(WalMarker::Begin)?;
+        self.transaction = Some(Transaction {
+            num_rows: self.num_rows,
+            next_id: self.next_id,
+            saved_pages: HashMap::new(),
+        });
+        Ok(())
+    }
+
+    /// Logs the commit, then flushes the transaction's changes to the file.
+    /// If the commit cannot be logged the transaction stays open.
+    pub fn commit_transaction(&mut self) -> io::Result<()> {
+        if self.transaction.is_none() {
+            return Ok(());
+        }
+        self.wal_mark(WalMarker::Commit)?;
+        self.transaction = None;
+        self.flush_dirty()
+    }
+
+    /// Restores the row count, next id and every page changed since `begin`,
+    /// and logs the rollback so replay drops the transaction's writes.
+    pub fn rollback_transaction(&mut self) -> io::Result<()> {
+        let Some(transaction) = self.transaction.take() else {
+            return Ok(());
+        };
+        let cache = self.cache_mut();
+        for (page_num, page) in transaction.saved_pages {
+            // a page allocated in the transaction may have been evicted to disk
+            if page.is_none() {
+                cache.on_disk[page_num] = false;
+            }
+            cache.pages[page_num] = page;
+            cache.dirty[page_num] = true;
+        }
+        self.num_rows = transaction.num_rows;
+        self.next_id = transaction.next_id;
+        self.invalidate_index();
+        self.wal_mark(WalMarker::Rollback)
+    }
+
+    fn save_page_for_rollback(&mut self, page_num: usize) {
+        let Some(transaction) = &self.transaction else {
+            return;
+        };
+        if transaction.saved_pages.contains_key(&page_num) {
+            return;
+        }
+        // the saved copy must be the page's real contents, not "not loaded"
+        self.load_page_or_panic(&mut self.lock_cache(), page_num);
+        let page = self.cache_mut().pages[page_num].clone();
+        if let Some(transaction) = &mut self.transaction {
+            transaction.saved_pages.insert(page_num, page);
+        }
+    }
+
+    /// Removes every row and releases all pages.
+    pub fn clear(&mut self) {
+        for page_num in 0..self.config.max_pages {
+            self.save_page_for_rollback(page_num);
+        }
+        self.num_rows = 0;
+        let cache = self.cache_mut();
+        cache.pages.iter_mut().for_each(|page| *page = None);
+        cache.dirty.iter_mut().for_each(|dirty| *dirty = false);
+        cache.on_disk.iter_mut().for_each(|on_disk| *on_disk = false);
+        self.invalidate_index();
+    }
+
+    /// Pages currently resident in memory.
+    pub fn allocated_pages(&self) -> usize {
+        self.lock_cache().pages.iter().filter(|page| page.is_some()).count()
+    }
+
+    /// Allocates the pages `rows` rows need up front, so that inserting them
+    /// later writes into memory that is already there. Requests past
+    /// `max_rows` are cut down to it, and pages that already exist in memory
+    /// or in the file are left alone. A file-backed table stops once its
+    /// page cache is full rather than evicting pages to make room.
+    pub fn reserve(&mut self, rows: usize) {
+        let page_count = self.page_count(rows.min(self.max_rows()));
+        let page_size = self.config.page_size;
+        let bounded = self.file.is_some();
+        let cache = self.cache_mut();
+        for page_num in 0..page_count {
+            if bounded && cache.resident() >= cache.capacity {
+                break;
+            }
+            if cache.pages[page_num].is_none() && !cache.on_disk[page_num] {
+                cache.pages[page_num] = Some(vec![0; page_size].into_boxed_slice());
+            }
+        }
+    }
+
+    /// Bytes held by resident pages plus the username index, if it has
+    /// been built.
+    pub fn memory_usage(&self) -> usize {
+        self.allocated_pages() * self.config.page_size + self.index_memory_usage()
+    }
+
+    pub fn is_page_resident(&self, page_num: usize) -> bool {
+        self.lock_cache().pages[page_num].is_some()
+    }
+
+    /// How many times a page has been read from the backing file.
+    pub fn page_loads(&self) -> usize {
+        self.lock_cache().loads
+    }
+
+    /// Panics if a page fails to load; see `try_find_id`.
+    pub fn find_id(&self, id: u64) -> Option<usize> {
+        self.try_find_id(id).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `find_id`, but a page that fails to load from the file is a
+    /// `Read` error instead of a panic.
+    pub fn try_find_id(&self, id: u64) -> Result<Option<usize>, DbError> {
+        for i in self.try_live_indices() {
+            let i = i?;
+            if deserialize_id(&self.try_read_slot(i)?) == id {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Indices of the rows below `num_rows` that have not been deleted.
+    /// Panics if a page fails to load; see `try_live_indices`.
+    pub fn live_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.try_live_indices().map(|i| i.unwrap_or_else(|err| panic!("{}", err)))
+    }
+
+    /// Like `live_indices`, but yields a `Read` error for each row whose
+    /// page fails to load from the file.
+    pub fn try_live_indices(&self) -> impl Iterator<Item = Result<usize, DbError>> + '_ {
+        (0..self.num_rows).filter_map(|i| match self.try_read_slot(i) {
+            Ok(slot) => (!is_deleted_slot(&slot)).then_some(Ok(i)),
+            Err(err) => Some(Err(err)),
+        })
+    }
+
+    pub fn live_rows(&self) -> usize {
+        self.live_indices().count()
+    }
+
+    /// Like `live_rows`, but stops at the first page that fails to load.
+    pub fn try_live_rows(&self) -> Result<usize, DbError> {
+        self.try_live_indices().try_fold(0, |count, i| i.map(|_| count + 1))
+    }
+
+    /// Marks the row at `index` deleted, logging the change like any other
+    /// row write. Its slot stays in use until `vacuum`. Returns whether a
+    /// live row was there.
+    pub fn delete_row(&mut self, index: usize) -> Result<bool, DbError> {
+        if index >= self.num_rows {
+            return Ok(false);
+        }
+        let mut row = deserialize(&self.try_read_slot(index)?);
+        if row.is_deleted() {
+            return Ok(false);
+        }
+        row.nulls |= ROW_DELETED;
+        self.wal_append(index, &row)?;
+        serialize(&row, self.try_row_slot(index)?);
+        Ok(true)
+    }
+
+    /// Moves the live rows down over deleted ones so they are stored
+    /// contiguously from index 0, then releases the pages left empty. A
+    /// page that fails to load stops it before any row has moved.
+    pub fn vacuum(&mut self) -> Result<(), DbError> {
+        let live = self.try_live_indices().collect::<Result<Vec<usize>, DbError>>()?;
+        for (to, &from) in live.iter().enumerate() {
+            if to != from {
+                let bytes = self.try_read_slot(from)?;
+                self.try_row_slot(to)?.copy_from_slice(&bytes);
+            }
+        }
+        self.num_rows = live.len();
+        self.compact_pages();
+        self.invalidate_index();
+        Ok(())
+    }
+
+    /// Frees every page past the last one `num_rows` reaches, in memory and
+    /// in the file's page map, without moving any rows. A transaction can
+    /// still roll the freed pages back.
+    pub fn compact_pages(&mut self) {
+        for page_num in self.page_count(self.num_rows)..self.config.max_pages {
+            if !self.is_page_resident(page_num) && !self.lock_cache().on_disk[page_num] {
+                continue;
+            }
+            self.save_page_for_rollback(page_num);
+            let cache = self.cache_mut();
+            cache.pages[page_num] = None;
+            cache.dirty[page_num] = false;
+            cache.on_disk[page_num] = false;
+        }
+    }
+
+    pub fn contains_id(&self, id: u64) -> bool {
+        self.find_id(id).is_some()
+    }
+
+    /// The row at `index`, or `None` past the last row or for a deleted one.
+    pub fn get(&self, index: usize) -> Option<Row> {
+        if index >= self.num_rows {
+            return None;
+        }
+        Some(deserialize(&self.read_slot(index))).filter(|row| !row.is_deleted())
+    }
+
+    /// The live rows matching `filter`, or all of them, in row order. This is
+    /// the programmatic counterpart of `select where ...`.
+    /// Panics if a page fails to load.
+    pub fn select_rows(&self, filter: Option<&Filter>) -> Vec<Row> {
+        candidate_indices(self, filter, None)
+            .map(|i| deserialize(&self.read_slot(i.unwrap_or_else(|err| panic!("{}", err)))))
+            .filter(|row| filter.is_none_or(|filter| filter.matches(row)))
+            .collect()
+    }
+
+    /// Walks every live row checking what inserts normally guarantee: ids
+    /// are unique and below `next_id`, and strings are valid UTF-8. Pages
+    /// that fail to load from the file are reported too. Worth
+    /// running on a table built from an import or a recovered file. Once
+    /// `next_id` has saturated at `u64::MAX` the row holding that id is
+    /// legitimately not below it, so the id bound is not checked.
+    pub fn validate(&self) -> Result<(), Vec<IntegrityError>> {
+        let mut errors = Vec::new();
+        let mut first_with_id = HashMap::new();
+        let rows_per_page = self.config.rows_per_page();
+        // a page that fails to load is reported once, not for each row on it
+        let mut unreadable = None;
+        for i in 0..self.num_rows {
+            let page = i / rows_per_page;
+            if unreadable == Some(page) {
+                continue;
+            }
+            let row = match self.try_read_slot(i) {
+                Ok(slot) => deserialize(&slot),
+                Err(err) => {
+                    let reason = match err {
+                        DbError::Read(err) => err.to_string(),
+                        err => err.to_string(),
+                    };
+                    errors.push(IntegrityError::UnreadablePage { page, reason });
+                    unreadable = Some(page);
+                    continue;
+                }
+            };
+            if row.is_deleted() {
+                continue;
+            }
+            if let Some(&first) = first_with_id.get(&row.id) {
+                errors.push(IntegrityError::DuplicateId { id: row.id, first, row: i });
+            } else {
+                first_with_id.insert(row.id, i);
+            }
+            if row.id >= self.next_id && self.next_id != u64::MAX {
+                errors.push(IntegrityError::IdNotBelowNextId { row: i, id: row.id, next_id: self.next_id });
+            }
+            for (column, bytes, null) in [
+                (Column::Username, &row.username[..], NULL_USERNAME),
+                (Column::Email, &row.email[..], NULL_EMAIL),
+            ] {
+                if row.nulls & null == 0 && from_utf8(bytes).is_err() {
+                    errors.push(IntegrityError::InvalidUtf8 { row: i, column });
+                }
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Appends `row`, logging it to the write-ahead log first, and returns
+    /// the index it was stored at.
+    pub fn insert_row(&mut self, row: Row) -> Result<usize, DbError> {
+        if self.read_only {
+            return Err(DbError::ReadOnly);
+        }
+        if self.num_rows >= self.max_rows() {
+            return Err(DbError::TableFull);
+        }
+        if self.try_find_id(row.id)?.is_some() {
+            return Err(DbError::DuplicateKey);
+        }
+        let index = self.num_rows;
+        self.wal_append(index, &row)?;
+        serialize(&row, self.slot_mut(index)?);
+        self.index_insert(index, &row);
+        self.num_rows += 1;
+        self.next_id = self.next_id.max(row.id.saturating_add(1));
+        Ok(index)
+    }
+
+    fn page_mut(&mut self, page_num: usize) -> Result<&mut [u8], ReadError> {
+        if page_num >= self.config.max_pages {
             panic!("Page number out of bounds");
         }
-        if self.pages[page_num].is_none() {
-            self.pages[page_num] = Some(Box::new([0; PAGE_SIZE]));
+        self.load_page_into(&mut self.lock_cache(), page_num)?;
+        self.save_page_for_rollback(page_num);
+        if !self.is_page_resident(page_num) {
+            self.make_room(&mut self.lock_cache(), page_num);
+        }
+        let page_size = self.config.page_size;
+        let cache = self.cache_mut();
+        cache.touch(page_num);
+        cache.dirty[page_num] = true;
+        Ok(cache.pages[page_num].get_or_insert_with(|| vec![0; page_size].into_boxed_slice()))
+    }
+
+    /// Hands out the row's bytes for writing and marks its page dirty.
+    /// Taking `&mut self` means writers need exclusive access to the table;
+    /// `SharedTable` relies on this to hand readers a shared lock only.
+    /// Panics if `index` is past the table's capacity or its page fails to
+    /// load; see `try_row_slot`.
+    pub fn row_slot(&mut self, index: usize) -> &mut [u8] {
+        self.try_row_slot(index).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `row_slot`, but an `index` past the table's capacity is an
+    /// `IndexOverflow` error instead of a panic, and a page that fails to
+    /// load from the file is a `Read` error.
+    pub fn try_row_slot(&mut self, index: usize) -> Result<&mut [u8], DbError> {
+        self.slot_location(index)?;
+        // the caller may overwrite any indexed column
+        self.invalidate_index();
+        self.slot_mut(index)
+    }
+
+    fn slot_mut(&mut self, index: usize) -> Result<&mut [u8], DbError> {
+        let (page_num, byte_offset) = self.slot_location(index)?;
+        let page = self.page_mut(page_num)?;
+        Ok(&mut page[byte_offset..byte_offset + ROW_SIZE])
+    }
+
+    /// The page holding row `index` and the row's byte offset within it.
+    /// The arithmetic is checked, so an out-of-range index is reported
+    /// rather than wrapping into some other row.
+    fn slot_location(&self, index: usize) -> Result<(usize, usize), DbError> {
+        let rows_per_page = self.config.rows_per_page();
+        let page_num = index / rows_per_page;
+        match (index % rows_per_page).checked_mul(ROW_SIZE) {
+            Some(byte_offset) if page_num < self.config.max_pages => Ok((page_num, byte_offset)),
+            _ => Err(DbError::IndexOverflow(index)),
+        }
+    }
+
+    /// Like `row_slot`, but for reading: returns a copy of the row's bytes
+    /// without marking the page dirty. A page that exists on disk is loaded
+    /// first; one that exists nowhere reads as zeroes without being allocated.
+    pub fn read_slot(&self, index: usize) -> [u8; ROW_SIZE] {
+        self.try_read_slot(index).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `read_slot`, but an `index` past the table's capacity is an
+    /// `IndexOverflow` error instead of a panic, and a page that fails to
+    /// load from the file is a `Read` error.
+    pub fn try_read_slot(&self, index: usize) -> Result<[u8; ROW_SIZE], DbError> {
+        let (page_num, byte_offset) = self.slot_location(index)?;
+        let mut cache = self.lock_cache();
+        self.load_page_into(&mut cache, page_num)?;
+        cache.touch(page_num);
+        let mut row = [0; ROW_SIZE];
+        if let Some(page) = &cache.pages[page_num] {
+            row.copy_from_slice(&page[byte_offset..byte_offset + ROW_SIZE]);
         }
-        let page = self.pages[page_num].as_mut().unwrap();
-        let row_offset = index % ROWS_PER_PAGE;
-        let byte_offset = row_offset * ROW_SIZE;
-        &mut page[byte_offset..byte_offset + ROW_SIZE]
+        Ok(row)
     }
 }
 
 pub fn serialize(row: &Row, dest: &mut [u8]) {
+    assert!(dest.len() >= ROW_SIZE, "row buffer holds {} bytes, need {}", dest.len(), ROW_SIZE);
     unsafe {
         ptr::copy_nonoverlapping(
-            &row.id as *const u32 as *const u8,
+            &row.id as *const u64 as *const u8,
             dest.as_mut_ptr().add(COLUMN_ID_OFFSET),
             COLUMN_ID_SIZE,
         );
@@ -106,18 +774,23 @@ pub fn serialize(row: &Row, dest: &mut [u8]) {
             COLUMN_EMAIL_SIZE,
         );
     }
+    dest[COLUMN_NULLS_OFFSET] = row.nulls;
 }
 
+/// Decodes a row from the first `ROW_SIZE` bytes of `src`, panicking if it
+/// is shorter. Use `try_deserialize` for bytes that may be malformed.
 pub fn deserialize(src: &[u8]) -> Row {
+    assert!(src.len() >= ROW_SIZE, "row buffer holds {} bytes, need {}", src.len(), ROW_SIZE);
     let mut row = Row {
         id: 0,
         username: [0; COLUMN_USERNAME_SIZE],
         email: [0; COLUMN_EMAIL_SIZE],
+        nulls: src[COLUMN_NULLS_OFFSET],
     };
     unsafe {
         ptr::copy_nonoverlapping(
             src.as_ptr().add(COLUMN_ID_OFFSET),
-            &mut row.id as *mut u32 as *mut u8,
+            &mut row.id as *mut u64 as *mut u8,
             COLUMN_ID_SIZE,
         );
         ptr::copy_nonoverlapping(
@@ -134,11 +807,40 @@ pub fn deserialize(src: &[u8]) -> Row {
     row
 }
 
-macro_rules! scan {
-    ( $string:expr, $sep:expr, $( $x:ty ),+ ) => {{
-        let mut iter = $string.split($sep);
-        ($(iter.next().and_then(|word| word.parse::<$x>().ok()),)*)
-    }}
+#[derive(Debug, PartialEq)]
+pub enum DeserializeError {
+    WrongLength(usize),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::WrongLength(len) => {
+                write!(f, "row is {} bytes, expected {}", len, ROW_SIZE)
+            }
+        }
+    }
+}
+
+impl Error for DeserializeError {}
+
+/// Like `deserialize`, but rejects input that is not exactly one row long
+/// instead of panicking. Every byte pattern of the right length decodes.
+pub fn try_deserialize(src: &[u8]) -> Result<Row, DeserializeError> {
+    let bytes: &[u8; ROW_SIZE] = src
+        .try_into()
+        .map_err(|_| DeserializeError::WrongLength(src.len()))?;
+    Ok(Row::from_bytes(bytes))
+}
+
+fn is_deleted_slot(src: &[u8]) -> bool {
+    src[COLUMN_NULLS_OFFSET] & ROW_DELETED != 0
+}
+
+fn deserialize_id(src: &[u8]) -> u64 {
+    let mut id = [0; COLUMN_ID_SIZE];
+    id.copy_from_slice(&src[COLUMN_ID_OFFSET..COLUMN_ID_OFFSET + COLUMN_ID_SIZE]);
+    u64::from_ne_bytes(id)
 }
 
 #[derive(Debug)]
@@ -148,136 +850,2258 @@ pub enum MetaCommandResult {
     Unrecognized,
 }
 
+/// Why `parse` rejected a statement.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    Syntax(Option<String>),
+    /// `column` is 1-based, in characters.
+    SyntaxAt { column: usize, expected: &'static str },
+    /// An insert that ran out of fields: `parsed` pairs each field read so far
+    /// with its text, `missing` names the fields still expected.
+    MissingFields {
+        column: usize,
+        parsed: Vec<(&'static str, String)>,
+        missing: Vec<&'static str>,
+    },
+    StringTooLong,
+    InvalidEmail(String),
+    Unrecognized,
+}
+
+/// Renders `got id=1, missing username and email`.
+fn describe_missing_fields(parsed: &[(&'static str, String)], missing: &[&'static str]) -> String {
+    let mut description = String::new();
+    if !parsed.is_empty() {
+        let parsed: Vec<String> = parsed.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        description.push_str(&format!("got {}, ", parsed.join(", ")));
+    }
+    description.push_str("missing ");
+    match missing.split_last() {
+        Some((last, [])) => description.push_str(last),
+        Some((last, rest)) => description.push_str(&format!("{} and {}", rest.join(", "), last)),
+        None => {}
+    }
+    description
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Syntax(None) => write!(f, "syntax error"),
+            ParseError::Syntax(Some(reason)) => write!(f, "syntax error: {}", reason),
+            ParseError::SyntaxAt { column, expected } => write!(f, "syntax error at column {}: expected {}", column, expected),
+            ParseError::MissingFields { column, parsed, missing } => {
+                write!(f, "syntax error at column {}: {}", column, describe_missing_fields(parsed, missing))
+            }
+            ParseError::StringTooLong => write!(f, "string is too long"),
+            ParseError::InvalidEmail(email) => write!(f, "invalid email: {}", email),
+            ParseError::Unrecognized => write!(f, "unrecognized keyword"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
 #[derive(Debug)]
 pub enum PrepareResult {
     Success,
-    SyntaxError,
+    SyntaxError(Option<String>),
+    /// `column` is 1-based, in characters.
+    SyntaxErrorAt { column: usize, expected: &'static str },
+    MissingFields {
+        column: usize,
+        parsed: Vec<(&'static str, String)>,
+        missing: Vec<&'static str>,
+    },
+    StringTooLong,
+    InvalidEmail(String),
     Unrecognized,
 }
 
+impl From<ParseError> for PrepareResult {
+    fn from(err: ParseError) -> Self {
+        match err {
+            ParseError::Syntax(reason) => PrepareResult::SyntaxError(reason),
+            ParseError::SyntaxAt { column, expected } => PrepareResult::SyntaxErrorAt { column, expected },
+            ParseError::MissingFields { column, parsed, missing } => PrepareResult::MissingFields { column, parsed, missing },
+            ParseError::StringTooLong => PrepareResult::StringTooLong,
+            ParseError::InvalidEmail(email) => PrepareResult::InvalidEmail(email),
+            ParseError::Unrecognized => PrepareResult::Unrecognized,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ExecuteResult {
-    Success,
+    Success { affected: usize },
+    /// A single row was inserted or replaced; `id` is the one it was stored
+    /// under, which the caller may not know for auto-assigned ids.
+    Inserted { id: u64 },
     TableFull,
+    DuplicateKey,
+    TransactionActive,
+    NoTransaction,
+    /// The table was opened with `Table::open_readonly`.
+    ReadOnly,
+    Io(io::Error),
+    /// A page the statement needed failed to load from the file, or failed
+    /// its checksum.
+    Read(ReadError),
+    /// A multi-row insert stopped at its first failing row; the rows before
+    /// it stay inserted.
+    BatchStopped { inserted: usize, cause: Box<ExecuteResult> },
 }
 
-#[derive(Debug)]
-pub enum StatementType {
-    Insert,
-    Select,
+/// The result for a statement stopped by a failed row operation.
+impl From<DbError> for ExecuteResult {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::TableFull => ExecuteResult::TableFull,
+            DbError::DuplicateKey => ExecuteResult::DuplicateKey,
+            DbError::ReadOnly => ExecuteResult::ReadOnly,
+            DbError::Io(err) => ExecuteResult::Io(err),
+            DbError::Read(err) => ExecuteResult::Read(err),
+            err => unreachable!("row operations cannot fail with {:?}", err),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct Statement {
-    statement_type: StatementType,
-    row_to_insert: Option<Row>,
+pub enum DbError {
+    Io(io::Error),
+    StringTooLong,
+    DuplicateKey,
+    TableFull,
+    NotFound,
+    ReadOnly,
+    /// A row index past what the table's pages can address.
+    IndexOverflow(usize),
+    /// A page could not be loaded from the file, or failed its checksum.
+    Read(ReadError),
+    Parse(String),
 }
 
-pub fn print_prompt<W: Write>(writer: &mut W) {
-    write!(writer, "rsql > ").unwrap();
-    writer.flush().expect("flush failed!");
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Io(err) => write!(f, "Error: {}", err),
+            DbError::StringTooLong => write!(f, "String is too long."),
+            DbError::DuplicateKey => write!(f, "Error: Duplicate key."),
+            DbError::TableFull => write!(f, "Error: Table full."),
+            DbError::NotFound => write!(f, "Error: Row not found."),
+            DbError::ReadOnly => write!(f, "Error: database is read-only"),
+            DbError::IndexOverflow(index) => write!(f, "Error: row index {} is out of range.", index),
+            DbError::Read(err) => write!(f, "Error: {}", err),
+            DbError::Parse(input) => write!(f, "Syntax Error in '{}'", input),
+        }
+    }
 }
 
-pub fn do_meta_command<W: Write>(buf: &str, writer: &mut W) -> MetaCommandResult {
-    if buf == ".exit" {
-        return MetaCommandResult::Exit;
-    }
-    writeln!(writer, "Unrecognized Command '{}'.", buf).unwrap();
-    MetaCommandResult::Unrecognized
+/// An invariant `Table::validate` found broken; `row` is the row index.
+#[derive(Debug, PartialEq)]
+pub enum IntegrityError {
+    /// `row` holds the same id as the earlier row `first`.
+    DuplicateId { id: u64, first: usize, row: usize },
+    IdNotBelowNextId { row: usize, id: u64, next_id: u64 },
+    InvalidUtf8 { row: usize, column: Column },
+    /// `page` failed to load from the file; `reason` says why.
+    UnreadablePage { page: usize, reason: String },
 }
 
-pub fn prepare_statement(buf: &str, statement: &mut Statement) -> PrepareResult {
-    if buf.starts_with("insert") {
-        statement.statement_type = StatementType::Insert;
-        let input = scan!(buf, char::is_whitespace, String, u32, String, String);
-        if let (Some(_), Some(id), Some(username), Some(email)) = input {
-            statement.row_to_insert = Some(Row::new(id, username, email));
-            return PrepareResult::Success;
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityError::DuplicateId { id, first, row } => {
+                write!(f, "row {}: duplicate id {} (also at row {})", row, id, first)
+            }
+            IntegrityError::IdNotBelowNextId { row, id, next_id } => {
+                write!(f, "row {}: id {} is not below the next id {}", row, id, next_id)
+            }
+            IntegrityError::InvalidUtf8 { row, column } => write!(f, "row {}: {} is not valid UTF-8", row, column.name()),
+            IntegrityError::UnreadablePage { page, reason } => write!(f, "page {}: {}", page, reason),
         }
-        return PrepareResult::SyntaxError;
-    }
-    if buf.starts_with("select") {
-        statement.statement_type = StatementType::Select;
-        return PrepareResult::Success;
     }
-    PrepareResult::Unrecognized
 }
 
-pub fn execute_statement<W: Write>(table: &mut Table, statement: &Statement, writer: &mut W) -> ExecuteResult {
-    match statement.statement_type {
-        StatementType::Insert => {
-            if let Some(row) = &statement.row_to_insert {
-                execute_insert(table, row)
-            } else {
-                ExecuteResult::Success
-            }
+impl Error for IntegrityError {}
+
+impl Error for DbError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DbError::Io(err) => Some(err),
+            DbError::Read(err) => Some(err),
+            _ => None,
         }
-        StatementType::Select => execute_select(table, writer),
     }
 }
 
-pub fn execute_insert(table: &mut Table, row: &Row) -> ExecuteResult {
-    if table.num_rows >= MAX_ROWS {
-        return ExecuteResult::TableFull;
+impl From<io::Error> for DbError {
+    fn from(err: io::Error) -> Self {
+        DbError::Io(err)
     }
-    serialize(row, table.row_slot(table.num_rows));
-    table.num_rows += 1;
-    ExecuteResult::Success
 }
 
-pub fn execute_select<W: Write>(table: &mut Table, writer: &mut W) -> ExecuteResult {
-    for i in 0..table.num_rows {
-        let row = deserialize(table.row_slot(i));
-        row.write(writer);
+impl From<ReadError> for DbError {
+    fn from(err: ReadError) -> Self {
+        DbError::Read(err)
     }
-    ExecuteResult::Success
 }
 
-pub fn run_repl<R: io::BufRead, W: Write>(table: &mut Table, reader: &mut R, writer: &mut W) {
-    let mut input_buffer = String::new();
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementType {
+    /// One row, or several for `insert (..),(..)`. `replace` is
+    /// `insert or replace`; with `auto_id` the row takes its id from
+    /// `Table::next_id`.
+    Insert { rows: Vec<Row>, replace: bool, auto_id: bool },
+    Select(Select),
+    /// Every row, or only those matching `filter`.
+    Delete { filter: Option<Filter> },
+    /// `update <id> set ...`: the columns to overwrite, with NULL as `None`.
+    Update { id: u64, assignments: Vec<(Column, Option<String>)> },
+    Begin,
+    Commit,
+    Rollback,
+}
 
-    loop {
-        print_prompt(writer);
-        input_buffer.clear();
+impl StatementType {
+    /// Whether running the statement can change rows.
+    pub fn is_write(&self) -> bool {
+        matches!(self, StatementType::Insert { .. } | StatementType::Delete { .. } | StatementType::Update { .. })
+    }
+}
 
-        if reader.read_line(&mut input_buffer).is_err() {
-            writeln!(writer, "Error reading input").unwrap();
-            break;
-        }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Id,
+    Username,
+    Email,
+}
 
-        let input = input_buffer.trim();
+impl Column {
+    pub const ALL: [Column; 3] = [Column::Id, Column::Username, Column::Email];
 
-        if input.starts_with('.') {
-            match do_meta_command(input, writer) {
-                MetaCommandResult::Exit => break,
-                MetaCommandResult::Unrecognized => continue,
-                MetaCommandResult::Success => continue,
-            }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Username => "username",
+            Column::Email => "email",
         }
+    }
 
-        let mut statement = Statement {
-            statement_type: StatementType::Insert,
-            row_to_insert: None,
-        };
+    pub fn from_name(name: &str) -> Option<Column> {
+        match name.to_ascii_lowercase().as_str() {
+            "id" => Some(Column::Id),
+            "username" => Some(Column::Username),
+            "email" => Some(Column::Email),
+            _ => None,
+        }
+    }
+}
 
-        match prepare_statement(input, &mut statement) {
-            PrepareResult::Success => {
-                match execute_statement(table, &statement, writer) {
-                    ExecuteResult::Success => {},
-                    ExecuteResult::TableFull => writeln!(writer, "Row not inserted, table full '{}'", statement.row_to_insert.expect("Row not initialized panic").to_string()).unwrap()
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Min,
+    Max,
+    Avg,
+}
 
-                }
-            }
-            PrepareResult::SyntaxError => writeln!(writer, "Syntax Error in '{}'", input).unwrap(),
-            PrepareResult::Unrecognized => writeln!(writer, "Unrecognized keyword at start of '{}'", input).unwrap(),
+impl Aggregate {
+    pub fn from_name(name: &str) -> Option<Aggregate> {
+        match name.to_ascii_lowercase().as_str() {
+            "min" => Some(Aggregate::Min),
+            "max" => Some(Aggregate::Max),
+            "avg" => Some(Aggregate::Avg),
+            _ => None,
         }
     }
 }
 
-// unit tests
+/// `select first` or `select last`: only the earliest or latest stored row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RowEnd {
+    First,
+    Last,
+}
+
+impl RowEnd {
+    pub fn from_name(name: &str) -> Option<RowEnd> {
+        match name.to_ascii_lowercase().as_str() {
+            "first" => Some(RowEnd::First),
+            "last" => Some(RowEnd::Last),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Like { column: Column, pattern: String },
+    /// `id between low and high`, inclusive at both ends.
+    Between { low: u64, high: u64 },
+    /// `username = value`, answered from the username index.
+    UsernameEquals(String),
+    IdEquals(u64),
+}
+
+impl Filter {
+    pub fn matches(&self, row: &Row) -> bool {
+        match self {
+            Filter::Like { column, pattern } => {
+                let value = match column {
+                    Column::Username => row.username_str(),
+                    Column::Email => row.email_str(),
+                    Column::Id => return false,
+                };
+                // NULL matches no pattern, as in SQL
+                value.is_some_and(|value| like_match(pattern, &value))
+            }
+            Filter::Between { low, high } => (*low..=*high).contains(&row.id),
+            Filter::UsernameEquals(username) => row.username_str().is_some_and(|value| value == *username),
+            Filter::IdEquals(id) => row.id == *id,
+        }
+    }
+}
+
+/// Renders the filter as it would be written after `where`.
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::Like { column, pattern } => write!(f, "{} like {}", column.name(), pattern),
+            Filter::Between { low, high } => write!(f, "id between {} and {}", low, high),
+            Filter::UsernameEquals(username) => write!(f, "username = {}", username),
+            Filter::IdEquals(id) => write!(f, "id = {}", id),
+        }
+    }
+}
+
+/// SQL `LIKE` matching: `%` matches any run of characters (including none)
+/// and `_` matches exactly one. Matching is case-sensitive.
+pub fn like_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `%` seen and the text position it was tried at,
+    // so a failed match can backtrack by letting the `%` absorb one more char.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '_' || pattern[p] == text[t]) && pattern[p] != '%' {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '%' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, star_t)) = backtrack {
+            p = star + 1;
+            t = star_t + 1;
+            backtrack = Some((star, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderBy {
+    pub column: Column,
+    pub descending: bool,
+}
+
+/// A `select` as written. Only one of `count`, `aggregate` and `row_end`
+/// is set, and with none of them `columns` are printed for each row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Select {
+    /// The projected columns; empty means every column.
+    pub columns: Vec<Column>,
+    pub count: bool,
+    pub aggregate: Option<Aggregate>,
+    pub row_end: Option<RowEnd>,
+    pub filter: Option<Filter>,
+    pub order_by: Option<OrderBy>,
+    /// `select distinct`: only the first row with each id is printed.
+    pub distinct: bool,
+    /// The statement's own `limit` clause, which wins over the session's.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Statement {
+    statement_type: StatementType,
+    // `explain <statement>`: describe what would run instead of running it
+    explain: bool,
+}
+
+/// An insert of no rows, which does nothing when run.
+impl Default for Statement {
+    fn default() -> Self {
+        Statement {
+            statement_type: StatementType::Insert { rows: Vec::new(), replace: false, auto_id: false },
+            explain: false,
+        }
+    }
+}
+
+/// Read access to what `parse` produced.
+impl Statement {
+    pub fn statement_type(&self) -> &StatementType {
+        &self.statement_type
+    }
+
+    pub fn is_explain(&self) -> bool {
+        self.explain
+    }
+}
+
+/// Session settings that shape how a select runs and prints, set by meta
+/// commands and passed in alongside each statement.
+#[derive(Debug, Clone, Default)]
+pub struct SelectSettings {
+    pub mode: OutputMode,
+    /// `.width` overrides for column mode, in `Column::ALL` order.
+    pub widths: [Option<usize>; 3],
+    /// `.raw`: write every stored byte in list form, whatever the mode.
+    pub raw: bool,
+    /// List mode's field separator from `.separator`; `None` is a space.
+    pub separator: Option<String>,
+    /// Most rows printed by a select without its own `limit`; `.limit`.
+    pub default_limit: Option<usize>,
+    /// Most rows a select reads before giving up, matched or not;
+    /// `.scanlimit`. With `order by` every row is still read to sort, and
+    /// the cap only bounds how many sorted rows are examined for output.
+    pub scan_limit: Option<usize>,
+}
+
+/// How `execute_select` formats rows, chosen with `.mode`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputMode {
+    /// Space-separated values, one row per line.
+    #[default]
+    List,
+    Csv,
+    /// A single JSON array of row objects.
+    Json,
+    /// An aligned table with a header row.
+    Column,
+}
+
+impl OutputMode {
+    pub fn from_name(name: &str) -> Option<OutputMode> {
+        match name.to_ascii_lowercase().as_str() {
+            "list" => Some(OutputMode::List),
+            "csv" => Some(OutputMode::Csv),
+            "json" => Some(OutputMode::Json),
+            "column" => Some(OutputMode::Column),
+            _ => None,
+        }
+    }
+}
+
+/// Session settings changed by meta commands and consulted by `run_repl`.
+#[derive(Debug, Default)]
+pub struct ReplState {
+    pub timer: bool,
+    pub mode: OutputMode,
+    pub verbose: bool,
+    // repeat each input line before its output, for scripts and pipes
+    pub echo: bool,
+    /// Fixed column-mode widths set by `.width`, in `Column::ALL` order;
+    /// `None` sizes the column to its widest value.
+    pub widths: [Option<usize>; 3],
+    /// Rows changed by the most recent insert, update or delete, for
+    /// `.changes`.
+    pub changes: usize,
+    /// Most rows a select prints unless it has its own `limit`; set by
+    /// `.limit`.
+    pub limit: Option<usize>,
+    /// Most rows a select reads, whether or not they match, before it stops
+    /// with a notice; set by `.scanlimit`. A select with `order by` still
+    /// reads every row to sort them, so there the cap only limits output.
+    pub scan_limit: Option<usize>,
+    /// Lines of statement output `run_repl` prints before pausing for input;
+    /// set by `.pager`.
+    pub pager: Option<usize>,
+    /// Selects print each row's stored bytes, padding and all; set by `.raw`.
+    pub raw: bool,
+    /// What list mode puts between fields, a single space when `None`; set
+    /// by `.separator`.
+    pub separator: Option<String>,
+    // stop a `.read` script at its first failing line
+    pub bail: bool,
+    // where statement results go after `.output <path>`; prompts and errors
+    // stay on the REPL's writer
+    output: Option<io::BufWriter<File>>,
+    // how many `.read` scripts are running inside one another
+    read_depth: usize,
+}
+
+impl ReplState {
+    /// The settings statements run from this session select with.
+    fn select_settings(&self) -> SelectSettings {
+        SelectSettings {
+            mode: self.mode,
+            widths: self.widths,
+            raw: self.raw,
+            separator: self.separator.clone(),
+            default_limit: self.limit,
+            scan_limit: self.scan_limit,
+        }
+    }
+}
+
+fn parse_toggle(argument: &str) -> Option<bool> {
+    match argument.to_ascii_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// The `.separator` argument as the text to put between fields. Since the
+/// argument is trimmed, whitespace separators are given by name.
+fn parse_separator(argument: &str) -> Option<String> {
+    match argument.to_ascii_lowercase().as_str() {
+        "" => None,
+        "tab" | "\\t" => Some("\t".to_string()),
+        "space" => Some(" ".to_string()),
+        "comma" => Some(",".to_string()),
+        "pipe" => Some("|".to_string()),
+        _ => Some(argument.to_string()),
+    }
+}
+
+pub fn print_prompt<W: Write>(writer: &mut W) {
+    write!(writer, "rsql > ").unwrap();
+    writer.flush().expect("flush failed!");
+}
+
+pub fn print_schema<W: Write>(writer: &mut W) {
+    let columns = [
+        ("id", "u64".to_string(), COLUMN_ID_OFFSET, COLUMN_ID_SIZE),
+        ("username", format!("char[{}]", COLUMN_USERNAME_SIZE), COLUMN_USERNAME_OFFSET, COLUMN_USERNAME_SIZE),
+        ("email", format!("char[{}]", COLUMN_EMAIL_SIZE), COLUMN_EMAIL_OFFSET, COLUMN_EMAIL_SIZE),
+        ("nulls", "u8".to_string(), COLUMN_NULLS_OFFSET, COLUMN_NULLS_SIZE),
+    ];
+    for (name, ty, offset, size) in columns {
+        writeln!(writer, "{}: {} (offset {}, size {})", name, ty, offset, size).unwrap();
+    }
+    writeln!(writer, "row size: {}", ROW_SIZE).unwrap();
+}
+
+pub fn print_stats<W: Write>(table: &Table, writer: &mut W) {
+    let allocated_pages = table.allocated_pages();
+    writeln!(writer, "rows: {}", table.num_rows).unwrap();
+    let config = table.config();
+    writeln!(writer, "pages allocated: {}/{}", allocated_pages, config.max_pages).unwrap();
+    writeln!(writer, "memory used: {} bytes", table.memory_usage()).unwrap();
+    writeln!(writer, "capacity used: {:.2}%", table.num_rows as f64 * 100.0 / table.max_rows() as f64).unwrap();
+}
+
+/// Inserts `count` generated rows, with ids counting up from
+/// `Table::next_id`, stopping at the first one that fails. `next_id` stops
+/// at `u64::MAX`, so once a row holds that id the next insert is a
+/// duplicate.
+fn seed<W: Write>(table: &mut Table, count: usize, writer: &mut W) {
+    for seeded in 0..count {
+        let id = table.next_id;
+        let row = Row::new(id, format!("user{}", id), format!("user{}@example.com", id));
+        match table.insert_row(row) {
+            Ok(_) => {}
+            Err(DbError::ReadOnly) => {
+                writeln!(writer, "{}", DbError::ReadOnly).unwrap();
+                return;
+            }
+            Err(err) => {
+                writeln!(writer, "{}", err).unwrap();
+                writeln!(writer, "Seeded {} of {} rows.", seeded, count).unwrap();
+                return;
+            }
+        }
+    }
+    writeln!(writer, "Seeded {} rows.", count).unwrap();
+}
+
+pub fn do_meta_command<W: Write>(buf: &str, table: &mut Table, state: &mut ReplState, writer: &mut W) -> MetaCommandResult {
+    let command = buf.split_whitespace().next().unwrap_or("");
+    let argument = buf[command.len()..].trim();
+    match command.to_ascii_lowercase().as_str() {
+        ".exit" => MetaCommandResult::Exit,
+        ".schema" => {
+            print_schema(writer);
+            MetaCommandResult::Success
+        }
+        ".timer" => {
+            match parse_toggle(argument) {
+                Some(enabled) => state.timer = enabled,
+                None => writeln!(writer, "Usage: .timer on|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".verbose" => {
+            match parse_toggle(argument) {
+                Some(enabled) => state.verbose = enabled,
+                None => writeln!(writer, "Usage: .verbose on|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".echo" => {
+            match parse_toggle(argument) {
+                Some(enabled) => state.echo = enabled,
+                None => writeln!(writer, "Usage: .echo on|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".separator" => {
+            match parse_separator(argument) {
+                Some(separator) => state.separator = Some(separator),
+                None => writeln!(writer, "Usage: .separator <text>|tab|space|comma|pipe").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".raw" => {
+            match parse_toggle(argument) {
+                Some(enabled) => state.raw = enabled,
+                None => writeln!(writer, "Usage: .raw on|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".width" => {
+            let widths: Option<Vec<usize>> = argument.split_whitespace().map(|width| width.parse().ok()).collect();
+            match widths {
+                // 0 or a missing width goes back to sizing the column to fit
+                Some(widths) if widths.len() <= state.widths.len() => {
+                    state.widths = [None; 3];
+                    for (slot, width) in state.widths.iter_mut().zip(widths) {
+                        *slot = (width > 0).then_some(width);
+                    }
+                }
+                _ => writeln!(writer, "Usage: .width [id] [username] [email]").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".bail" => {
+            match parse_toggle(argument) {
+                Some(enabled) => state.bail = enabled,
+                None => writeln!(writer, "Usage: .bail on|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".output" => {
+            if let Some(mut file) = state.output.take() {
+                if let Err(err) = file.flush() {
+                    writeln!(writer, "Error writing output file: {}", err).unwrap();
+                }
+            }
+            if argument.is_empty() || argument == "stdout" {
+                return MetaCommandResult::Success;
+            }
+            match File::create(argument) {
+                Ok(file) => state.output = Some(io::BufWriter::new(file)),
+                Err(err) => writeln!(writer, "Error opening '{}': {}", argument, err).unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".vacuum" | ".drop" if table.is_read_only() => {
+            writeln!(writer, "{}", DbError::ReadOnly).unwrap();
+            MetaCommandResult::Success
+        }
+        ".vacuum" => {
+            if let Err(err) = table.vacuum() {
+                writeln!(writer, "{}", err).unwrap();
+                return MetaCommandResult::Success;
+            }
+            if let Err(err) = table.flush_dirty() {
+                writeln!(writer, "Error saving database: {}", err).unwrap();
+            }
+            MetaCommandResult::Success
+        }
+        ".read" => {
+            if argument.is_empty() {
+                writeln!(writer, "Usage: .read <path>").unwrap();
+                return MetaCommandResult::Success;
+            }
+            read_script(argument, table, state, writer)
+        }
+        ".stats" => {
+            print_stats(table, writer);
+            MetaCommandResult::Success
+        }
+        ".seed" => {
+            match argument.parse::<usize>() {
+                Ok(count) => seed(table, count, writer),
+                Err(_) => writeln!(writer, "Usage: .seed <count>").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".limit" => {
+            match argument.parse::<usize>() {
+                Ok(limit) => state.limit = (limit > 0).then_some(limit),
+                Err(_) if argument.eq_ignore_ascii_case("off") => state.limit = None,
+                Err(_) => writeln!(writer, "Usage: .limit <n>|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".scanlimit" => {
+            match argument.parse::<usize>() {
+                Ok(scan_limit) => state.scan_limit = (scan_limit > 0).then_some(scan_limit),
+                Err(_) if argument.eq_ignore_ascii_case("off") => state.scan_limit = None,
+                Err(_) => writeln!(writer, "Usage: .scanlimit <n>|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".pager" => {
+            let mut arguments = argument.split_whitespace();
+            let enabled = arguments.next().and_then(parse_toggle);
+            let lines = arguments.next().map_or(Ok(DEFAULT_PAGER_LINES), str::parse::<usize>);
+            match (enabled, lines, arguments.next()) {
+                (Some(true), Ok(lines), None) if lines > 0 => state.pager = Some(lines),
+                (Some(false), _, None) => state.pager = None,
+                _ => writeln!(writer, "Usage: .pager on [lines]|off").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".changes" => {
+            writeln!(writer, "{}", state.changes).unwrap();
+            MetaCommandResult::Success
+        }
+        ".check" => {
+            match table.validate() {
+                Ok(()) => writeln!(writer, "ok").unwrap(),
+                Err(errors) => {
+                    for error in errors {
+                        writeln!(writer, "{}", error).unwrap();
+                    }
+                }
+            }
+            MetaCommandResult::Success
+        }
+        ".mem" => {
+            writeln!(writer, "memory used: {} bytes", table.memory_usage()).unwrap();
+            MetaCommandResult::Success
+        }
+        ".mode" => {
+            match OutputMode::from_name(argument) {
+                Some(mode) => state.mode = mode,
+                None => writeln!(writer, "Usage: .mode list|csv|json|column").unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        ".save" => {
+            if argument.is_empty() {
+                writeln!(writer, "Usage: .save <path>").unwrap();
+            } else if let Err(err) = table.save(argument) {
+                writeln!(writer, "Error saving to '{}': {}", argument, err).unwrap();
+            }
+            MetaCommandResult::Success
+        }
+        ".backup" => {
+            if argument.is_empty() {
+                writeln!(writer, "Usage: .backup <path>").unwrap();
+            } else if let Err(err) = table.backup(argument) {
+                writeln!(writer, "Error backing up to '{}': {}", argument, err).unwrap();
+            }
+            MetaCommandResult::Success
+        }
+        ".open" => {
+            if argument.is_empty() {
+                writeln!(writer, "Usage: .open <path>").unwrap();
+                return MetaCommandResult::Success;
+            }
+            if let Err(err) = table.flush_dirty() {
+                writeln!(writer, "Error saving current database: {}", err).unwrap();
+                return MetaCommandResult::Success;
+            }
+            // The REPL only borrows the table, so the opened one is swapped
+            // in place rather than handed back to the caller. The current
+            // table lets go of its lock first, or reopening its own path
+            // would find the file locked.
+            let previous = std::mem::take(table);
+            if let Err(err) = previous.unlock_file() {
+                *table = previous;
+                writeln!(writer, "Error unlocking current database: {}", err).unwrap();
+                return MetaCommandResult::Success;
+            }
+            match Table::open(argument) {
+                Ok(opened) => *table = opened,
+                Err(err) => {
+                    writeln!(writer, "Error opening '{}': {}", argument, err).unwrap();
+                    if let Err(err) = previous.relock_file() {
+                        writeln!(writer, "Warning: could not lock the current database again: {}", err).unwrap();
+                    }
+                    *table = previous;
+                }
+            }
+            MetaCommandResult::Success
+        }
+        ".export" => {
+            if argument.is_empty() {
+                writeln!(writer, "Usage: .export <path>").unwrap();
+                return MetaCommandResult::Success;
+            }
+            let result = File::create(argument).and_then(|mut file| export_csv(table, &mut file));
+            if let Err(err) = result {
+                writeln!(writer, "Error exporting to '{}': {}", argument, err).unwrap();
+            }
+            MetaCommandResult::Success
+        }
+        ".dump" => {
+            if let Err(err) = dump(table, writer) {
+                writeln!(writer, "Error dumping table: {}", err).unwrap();
+            }
+            MetaCommandResult::Success
+        }
+        ".drop" => {
+            if let Err(err) = table.reset() {
+                writeln!(writer, "Error dropping table: {}", err).unwrap();
+            }
+            MetaCommandResult::Success
+        }
+        ".import" => {
+            if argument.is_empty() {
+                writeln!(writer, "Usage: .import <path>").unwrap();
+                return MetaCommandResult::Success;
+            }
+            // `.jsonl` files hold JSON Lines; anything else is read as CSV
+            let jsonl = Path::new(argument).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("jsonl"));
+            let result = File::open(argument).map_err(ImportError::Io).and_then(|file| {
+                let mut reader = io::BufReader::new(file);
+                if jsonl {
+                    import_jsonl(table, &mut reader)
+                } else {
+                    import_csv(table, &mut reader)
+                }
+            });
+            match result {
+                Ok(count) => writeln!(writer, "Imported {} rows.", count).unwrap(),
+                Err(err) => writeln!(writer, "Error importing '{}': {}", argument, err).unwrap(),
+            }
+            MetaCommandResult::Success
+        }
+        _ => {
+            writeln!(writer, "Unrecognized Command '{}'.", buf).unwrap();
+            MetaCommandResult::Unrecognized
+        }
+    }
+}
+
+fn starts_with_keyword(buf: &str, keyword: &str) -> bool {
+    buf.get(..keyword.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(keyword))
+}
+
+/// Whether the first token of `buf` is `keyword` as a whole word, so that
+/// `deleted` is not `delete`. Keywords hold no quotes, so the first run of
+/// non-whitespace is the token `tokenize` would produce.
+fn first_token_is(buf: &str, keyword: &str) -> bool {
+    buf.split_whitespace().next().is_some_and(|word| is_keyword(word, keyword))
+}
+
+/// The statement after a leading `explain` keyword, if there is one.
+fn strip_explain(buf: &str) -> Option<&str> {
+    let rest = buf.get("explain".len()..)?;
+    if starts_with_keyword(buf, "explain") && rest.starts_with(char::is_whitespace) {
+        Some(rest.trim_start())
+    } else {
+        None
+    }
+}
+
+/// Parses one line of input into a new `Statement`.
+pub fn parse(input: &str) -> Result<Statement, ParseError> {
+    if let Some(rest) = strip_explain(input) {
+        // error columns count from the start of the whole line
+        let shift = input.chars().count() - rest.chars().count();
+        let mut statement = parse(rest).map_err(|err| match err {
+            ParseError::SyntaxAt { column, expected } => ParseError::SyntaxAt { column: column + shift, expected },
+            ParseError::MissingFields { column, parsed, missing } => {
+                ParseError::MissingFields { column: column + shift, parsed, missing }
+            }
+            err => err,
+        })?;
+        statement.explain = true;
+        return Ok(statement);
+    }
+    Ok(Statement { statement_type: parse_statement_type(input)?, explain: false })
+}
+
+/// Adapter over `parse` for callers that reuse a `Statement`.
+pub fn prepare_statement(buf: &str, statement: &mut Statement) -> PrepareResult {
+    match parse(buf) {
+        Ok(parsed) => {
+            *statement = parsed;
+            PrepareResult::Success
+        }
+        Err(err) => err.into(),
+    }
+}
+
+fn parse_statement_type(buf: &str) -> Result<StatementType, ParseError> {
+    if starts_with_keyword(buf, "insert") {
+        let rest = buf["insert".len()..].trim_start();
+        if rest.starts_with('(') {
+            let rows = prepare_insert_tuples(rest)?;
+            return Ok(StatementType::Insert { rows, replace: false, auto_id: false });
+        }
+        let marked = match tokenize(buf) {
+            Ok(tokens) => tokens,
+            Err(_) => return Err(ParseError::Syntax(None)),
+        };
+        let replace = marked.len() > 2 && is_keyword(&marked[1].text, "or") && is_keyword(&marked[2].text, "replace");
+        let fields = if replace { &marked[3..] } else { &marked[1..] };
+        // `insert <username> <email>` takes its id from `Table::next_id`; a
+        // bare number first reads as an id with the email left out
+        let auto_id = !replace && fields.len() == 2 && (fields[0].quoted || fields[0].text.parse::<u64>().is_err());
+        // missing fields are reported just past the end of the line, along
+        // with whatever was read before them
+        let mut parsed = Vec::new();
+        let missing = |parsed: Vec<(&'static str, String)>| {
+            let missing = ["id", "username", "email"][parsed.len()..].to_vec();
+            Err(ParseError::MissingFields { column: buf.chars().count() + 1, parsed, missing })
+        };
+        let (id, strings) = if auto_id {
+            (0, fields)
+        } else {
+            let Some((id, rest)) = fields.split_first() else {
+                return missing(parsed);
+            };
+            match id.text.parse::<u64>() {
+                Ok(value) => {
+                    parsed.push(("id", value.to_string()));
+                    (value, rest)
+                }
+                Err(_) => return Err(ParseError::SyntaxAt { column: id.start + 1, expected: "id" }),
+            }
+        };
+        let (username, email) = match strings {
+            [] => return missing(parsed),
+            [username] => {
+                parsed.push(("username", username.text.clone()));
+                return missing(parsed);
+            }
+            [username, email, ..] => (username, email),
+        };
+        let rows = vec![prepare_row(id, username, email)?];
+        return Ok(StatementType::Insert { rows, replace, auto_id });
+    }
+    if starts_with_keyword(buf, "select") {
+        return match tokenizer::words(buf) {
+            Ok(tokens) => prepare_select(&tokens[1..]).map(StatementType::Select),
+            Err(_) => Err(ParseError::Syntax(None)),
+        };
+    }
+    for (keyword, statement_type) in [
+        ("begin", StatementType::Begin),
+        ("commit", StatementType::Commit),
+        ("rollback", StatementType::Rollback),
+    ] {
+        if first_token_is(buf, keyword) {
+            return match tokenize(buf) {
+                Ok(tokens) if tokens.len() == 1 => Ok(statement_type),
+                _ => Err(ParseError::Syntax(None)),
+            };
+        }
+    }
+    if first_token_is(buf, "delete") {
+        return match tokenizer::words(buf) {
+            Ok(tokens) if tokens.len() == 1 => Ok(StatementType::Delete { filter: None }),
+            Ok(tokens) if is_keyword(&tokens[1], "where") => {
+                Ok(StatementType::Delete { filter: Some(prepare_filter(&tokens[2..])?) })
+            }
+            _ => Err(ParseError::Syntax(None)),
+        };
+    }
+    if starts_with_keyword(buf, "update") {
+        return prepare_update(buf["update".len()..].trim_start());
+    }
+    Err(ParseError::Unrecognized)
+}
+
+/// Parses `<id> set <column>=<value>, ...`. Each value is a single token,
+/// so it may be quoted or a bare NULL as in an insert.
+fn prepare_update(rest: &str) -> Result<StatementType, ParseError> {
+    let usage = || ParseError::Syntax(Some("expected 'update <id> set <column>=<value>, ...'".to_string()));
+    let (id, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let id = id.parse::<u64>().map_err(|_| ParseError::Syntax(Some(format!("invalid id '{}'", id))))?;
+    let (set, list) = rest.trim_start().split_once(char::is_whitespace).ok_or_else(usage)?;
+    if !is_keyword(set, "set") {
+        return Err(usage());
+    }
+    let mut assignments: Vec<(Column, Option<String>)> = Vec::new();
+    for assignment in tokenizer::split_unquoted(list, ',').map_err(|_| usage())? {
+        let (name, value) = assignment.split_once('=').ok_or_else(usage)?;
+        let column = match Column::from_name(name.trim()) {
+            Some(Column::Id) => return Err(ParseError::Syntax(Some("cannot set 'id'".to_string()))),
+            Some(column) => column,
+            None => return Err(ParseError::Syntax(Some(format!("unknown column '{}'", name.trim())))),
+        };
+        if assignments.iter().any(|&(assigned, _)| assigned == column) {
+            return Err(ParseError::Syntax(Some(format!("'{}' is set more than once", column.name()))));
+        }
+        let value = match tokenize(value) {
+            Ok(tokens) if tokens.len() == 1 => nullable_field(&tokens[0]),
+            _ => return Err(usage()),
+        };
+        let size = match column {
+            Column::Username => COLUMN_USERNAME_SIZE,
+            _ => COLUMN_EMAIL_SIZE,
+        };
+        if value.as_ref().is_some_and(|value| value.len() > size) {
+            return Err(ParseError::StringTooLong);
+        }
+        if column == Column::Email {
+            check_email(&value)?;
+        }
+        assignments.push((column, value));
+    }
+    Ok(StatementType::Update { id, assignments })
+}
+
+fn prepare_row(id: u64, username: &Token, email: &Token) -> Result<Row, ParseError> {
+    let (username, email) = (nullable_field(username), nullable_field(email));
+    check_email(&email)?;
+    Row::try_new_nullable(id, username, email).map_err(|_| ParseError::StringTooLong)
+}
+
+/// NULL and empty emails are both "no email"; anything else needs an '@'.
+fn check_email(email: &Option<String>) -> Result<(), ParseError> {
+    match email {
+        Some(email) if !email.is_empty() && !email.contains('@') => Err(ParseError::InvalidEmail(email.clone())),
+        _ => Ok(()),
+    }
+}
+
+/// Parses `(id,username,email),(id,username,email),...` into the rows to
+/// insert. Each field is a single token, so it may be quoted or a bare NULL
+/// just like in the space-separated form.
+fn prepare_insert_tuples(mut rest: &str) -> Result<Vec<Row>, ParseError> {
+    let mut rows = Vec::new();
+    loop {
+        let Some(end) = tokenizer::find_unquoted(rest, ')').map_err(|_| ParseError::Syntax(None))? else {
+            return Err(ParseError::Syntax(Some("unterminated value tuple".to_string())));
+        };
+        let fields: Vec<Token> = match tokenizer::split_unquoted(&rest[1..end], ',')
+            .map_err(|_| ParseError::Syntax(None))?
+            .into_iter()
+            .map(|field| match tokenize(field) {
+                Ok(mut tokens) if tokens.len() == 1 => tokens.pop(),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+        {
+            Some(fields) => fields,
+            None => return Err(ParseError::Syntax(None)),
+        };
+        let [id, username, email] = fields.as_slice() else {
+            return Err(ParseError::Syntax(Some(format!("expected 3 values, found {}", fields.len()))));
+        };
+        let Ok(id) = id.text.parse::<u64>() else {
+            return Err(ParseError::Syntax(None));
+        };
+        rows.push(prepare_row(id, username, email)?);
+        rest = rest[end + 1..].trim_start();
+        match rest.strip_prefix(',') {
+            Some(next) if next.trim_start().starts_with('(') => rest = next.trim_start(),
+            Some(_) => return Err(ParseError::Syntax(None)),
+            None if rest.is_empty() => break,
+            None => return Err(ParseError::Syntax(None)),
+        }
+    }
+    Ok(rows)
+}
+
+/// A bare NULL keyword stores a NULL column; a quoted "NULL" is a string.
+fn nullable_field(token: &Token) -> Option<String> {
+    if !token.quoted && is_keyword(&token.text, "null") {
+        None
+    } else {
+        Some(token.text.clone())
+    }
+}
+
+fn is_keyword(token: &str, keyword: &str) -> bool {
+    token.eq_ignore_ascii_case(keyword)
+}
+
+fn prepare_select(tokens: &[String]) -> Result<Select, ParseError> {
+    let mut select = Select::default();
+    let tokens = match tokens.split_first() {
+        Some((first, rest)) if is_keyword(first, "distinct") => {
+            select.distinct = true;
+            rest
+        }
+        _ => tokens,
+    };
+    // `limit <n>` comes last, after any where and order by
+    let tokens = match tokens {
+        [rest @ .., limit, count] if is_keyword(limit, "limit") => {
+            let count = count.parse().map_err(|_| ParseError::Syntax(Some(format!("invalid limit '{}'", count))))?;
+            select.limit = Some(count);
+            rest
+        }
+        _ => tokens,
+    };
+    if tokens.iter().any(|token| is_keyword(token, "limit")) {
+        return Err(ParseError::Syntax(Some("expected 'limit <n>' at the end".to_string())));
+    }
+    let where_position = tokens.iter().position(|token| is_keyword(token, "where"));
+    let order_position = tokens.iter().position(|token| is_keyword(token, "order"));
+    if let (Some(where_position), Some(order_position)) = (where_position, order_position) {
+        if order_position < where_position {
+            return Err(ParseError::Syntax(Some("'where' must come before 'order by'".to_string())));
+        }
+    }
+    let projection_end = where_position.or(order_position).unwrap_or(tokens.len());
+    let projection = &tokens[..projection_end];
+    if let Some(position) = where_position {
+        select.filter = Some(prepare_filter(&tokens[position + 1..order_position.unwrap_or(tokens.len())])?);
+    }
+    if let Some(position) = order_position {
+        select.order_by = Some(prepare_order_by(&tokens[position + 1..])?);
+    }
+    if projection.is_empty() {
+        return Ok(select);
+    }
+    if let [word] = projection {
+        if is_keyword(word, "count") {
+            select.count = true;
+            return Ok(select);
+        }
+        if let Some(aggregate) = Aggregate::from_name(word) {
+            select.aggregate = Some(aggregate);
+            return Ok(select);
+        }
+        if let Some(row_end) = RowEnd::from_name(word) {
+            // the row comes from a fixed end of the table, so ordering or
+            // limiting it would be ignored
+            for (clause, present) in [("order by", select.order_by.is_some()), ("limit", select.limit.is_some())] {
+                if present {
+                    let message = format!("'{}' cannot be combined with '{}'", word.to_ascii_lowercase(), clause);
+                    return Err(ParseError::Syntax(Some(message)));
+                }
+            }
+            select.row_end = Some(row_end);
+            return Ok(select);
+        }
+    }
+    let column_list = projection.join(" ");
+    for name in column_list.split(',').map(str::trim) {
+        match Column::from_name(name) {
+            Some(column) => select.columns.push(column),
+            None => return Err(ParseError::Syntax(Some(format!("unknown column '{}'", name)))),
+        }
+    }
+    Ok(select)
+}
+
+fn prepare_filter(tokens: &[String]) -> Result<Filter, ParseError> {
+    match tokens {
+        [column, like, pattern] if is_keyword(like, "like") => match Column::from_name(column) {
+            Some(column @ (Column::Username | Column::Email)) => Ok(Filter::Like { column, pattern: pattern.clone() }),
+            _ => Err(ParseError::Syntax(Some(format!("cannot apply 'like' to '{}'", column)))),
+        },
+        [column, equals, value] if equals == "=" => match Column::from_name(column) {
+            Some(Column::Username) => Ok(Filter::UsernameEquals(value.clone())),
+            Some(Column::Id) => match value.parse::<u64>() {
+                Ok(id) => Ok(Filter::IdEquals(id)),
+                Err(_) => Err(ParseError::Syntax(Some(format!("invalid id '{}'", value)))),
+            },
+            _ => Err(ParseError::Syntax(Some(format!("cannot apply '=' to '{}'", column)))),
+        },
+        [column, between, low, and, high] if is_keyword(between, "between") && is_keyword(and, "and") => {
+            if Column::from_name(column) != Some(Column::Id) {
+                return Err(ParseError::Syntax(Some(format!("cannot apply 'between' to '{}'", column))));
+            }
+            match (low.parse::<u64>(), high.parse::<u64>()) {
+                (Ok(low), Ok(high)) => Ok(Filter::Between { low, high }),
+                _ => Err(ParseError::Syntax(Some("'between' bounds must be ids".to_string()))),
+            }
+        }
+        _ => Err(ParseError::Syntax(Some(
+            "expected 'where <column> like <pattern>', 'where id between <low> and <high>' or 'where <column> = <value>'"
+                .to_string(),
+        ))),
+    }
+}
+
+fn prepare_order_by(tokens: &[String]) -> Result<OrderBy, ParseError> {
+    let (column, direction) = match tokens {
+        [by, column] if is_keyword(by, "by") => (column, None),
+        [by, column, direction] if is_keyword(by, "by") => (column, Some(direction)),
+        _ => return Err(ParseError::Syntax(Some("expected 'order by <column> [asc|desc]'".to_string()))),
+    };
+    let column = match Column::from_name(column) {
+        Some(column @ (Column::Id | Column::Username)) => column,
+        _ => return Err(ParseError::Syntax(Some(format!("cannot order by '{}'", column)))),
+    };
+    let descending = match direction {
+        None => false,
+        Some(direction) if is_keyword(direction, "asc") => false,
+        Some(direction) if is_keyword(direction, "desc") => true,
+        Some(direction) => return Err(ParseError::Syntax(Some(format!("unknown sort direction '{}'", direction)))),
+    };
+    Ok(OrderBy { column, descending })
+}
+
+/// Runs `statement`, with any select shaped by the session's `settings`.
+pub fn execute_statement<W: Write>(
+    table: &mut Table,
+    statement: &Statement,
+    settings: &SelectSettings,
+    writer: &mut W,
+) -> ExecuteResult {
+    if statement.explain {
+        return execute_explain(table, statement, settings, writer);
+    }
+    if table.is_read_only() && statement.statement_type.is_write() {
+        return ExecuteResult::ReadOnly;
+    }
+    match &statement.statement_type {
+        StatementType::Insert { rows, replace, auto_id } => match rows.as_slice() {
+            [row] if *auto_id => {
+                let row = Row { id: table.next_id, username: row.username, email: row.email, nulls: row.nulls };
+                execute_insert(table, &row)
+            }
+            [row] if *replace => execute_upsert(table, row),
+            [row] => execute_insert(table, row),
+            rows => execute_insert_batch(table, rows),
+        },
+        StatementType::Select(select) => execute_select(table, select, settings, writer),
+        StatementType::Delete { filter: Some(filter) } => execute_delete_where(table, filter),
+        StatementType::Delete { filter: None } => execute_delete(table),
+        StatementType::Update { id, assignments } => execute_update(table, *id, assignments),
+        StatementType::Begin => execute_begin(table),
+        StatementType::Commit => execute_commit(table),
+        StatementType::Rollback => execute_rollback(table),
+    }
+}
+
+/// `explain <statement>`: writes what `statement` would do, and how many
+/// rows it would read getting there, without touching the table.
+pub fn execute_explain<W: Write>(
+    table: &Table,
+    statement: &Statement,
+    settings: &SelectSettings,
+    writer: &mut W,
+) -> ExecuteResult {
+    let live_rows = match table.try_live_rows() {
+        Ok(live_rows) => live_rows,
+        Err(err) => return err.into(),
+    };
+    let scanned = match &statement.statement_type {
+        StatementType::Insert { rows, replace, auto_id } => {
+            let rows = rows.len();
+            let verb = if *replace { "insert or replace" } else { "insert" };
+            writeln!(writer, "{} {} {} into table", verb, rows, if rows == 1 { "row" } else { "rows" }).unwrap();
+            if *auto_id {
+                writeln!(writer, "id: {} (next id)", table.next_id).unwrap();
+            }
+            // each row is checked against every live id first
+            rows * live_rows
+        }
+        StatementType::Select(select) => {
+            writeln!(writer, "select from table").unwrap();
+            let indexed = matches!(select.filter, Some(Filter::UsernameEquals(_))) && select.order_by.is_none();
+            if let Some(filter) = &select.filter {
+                let access = if indexed { "username index" } else { "full scan" };
+                writeln!(writer, "filter: {} ({})", filter, access).unwrap();
+            }
+            if let Some(order_by) = select.order_by {
+                let direction = if order_by.descending { "desc" } else { "asc" };
+                writeln!(writer, "order by: {} {}", order_by.column.name(), direction).unwrap();
+            }
+            if let Some(limit) = select.limit.or(settings.default_limit) {
+                writeln!(writer, "limit: {}", limit).unwrap();
+            }
+            let candidates = candidate_indices(table, select.filter.as_ref(), select.order_by)
+                .try_fold(0, |count, i| i.map(|_| count + 1));
+            let candidates = match candidates {
+                Ok(candidates) => candidates,
+                Err(err) => return err.into(),
+            };
+            settings.scan_limit.map_or(candidates, |scan_limit| candidates.min(scan_limit))
+        }
+        StatementType::Delete { filter } => {
+            writeln!(writer, "delete from table").unwrap();
+            if let Some(filter) = filter {
+                writeln!(writer, "filter: {} (full scan)", filter).unwrap();
+            }
+            live_rows
+        }
+        StatementType::Update { id, assignments } => {
+            writeln!(writer, "update table").unwrap();
+            writeln!(writer, "filter: {} (full scan)", Filter::IdEquals(*id)).unwrap();
+            let columns: Vec<&str> = assignments.iter().map(|(column, _)| column.name()).collect();
+            writeln!(writer, "set: {}", columns.join(", ")).unwrap();
+            live_rows
+        }
+        StatementType::Begin => return explain_transaction("begin", writer),
+        StatementType::Commit => return explain_transaction("commit", writer),
+        StatementType::Rollback => return explain_transaction("rollback", writer),
+    };
+    writeln!(writer, "estimated rows scanned: {}", scanned).unwrap();
+    ExecuteResult::Success { affected: 0 }
+}
+
+fn explain_transaction<W: Write>(verb: &str, writer: &mut W) -> ExecuteResult {
+    writeln!(writer, "{} transaction", verb).unwrap();
+    ExecuteResult::Success { affected: 0 }
+}
+
+pub fn execute_begin(table: &mut Table) -> ExecuteResult {
+    if table.in_transaction() {
+        return ExecuteResult::TransactionActive;
+    }
+    match table.begin_transaction() {
+        Ok(()) => ExecuteResult::Success { affected: 0 },
+        Err(err) => ExecuteResult::Io(err),
+    }
+}
+
+pub fn execute_commit(table: &mut Table) -> ExecuteResult {
+    if !table.in_transaction() {
+        return ExecuteResult::NoTransaction;
+    }
+    match table.commit_transaction() {
+        Ok(()) => ExecuteResult::Success { affected: 0 },
+        Err(err) => ExecuteResult::Io(err),
+    }
+}
+
+pub fn execute_rollback(table: &mut Table) -> ExecuteResult {
+    if !table.in_transaction() {
+        return ExecuteResult::NoTransaction;
+    }
+    if let Err(err) = table.rollback_transaction() {
+        return ExecuteResult::Io(err);
+    }
+    checkpoint(table, 0)
+}
+
+pub fn execute_delete(table: &mut Table) -> ExecuteResult {
+    let affected = match table.try_live_rows() {
+        Ok(affected) => affected,
+        Err(err) => return err.into(),
+    };
+    table.clear();
+    checkpoint(table, affected)
+}
+
+/// `delete where <filter>`: marks the matching rows deleted in place.
+pub fn execute_delete_where(table: &mut Table, filter: &Filter) -> ExecuteResult {
+    let matching = match matching_indices(table, filter) {
+        Ok(matching) => matching,
+        Err(err) => return err.into(),
+    };
+    for &i in &matching {
+        if let Err(err) = table.delete_row(i) {
+            return err.into();
+        }
+    }
+    ExecuteResult::Success { affected: matching.len() }
+}
+
+/// Every live row matching `filter`, found by reading the whole table.
+fn matching_indices(table: &Table, filter: &Filter) -> Result<Vec<usize>, DbError> {
+    let mut matching = Vec::new();
+    for i in table.try_live_indices() {
+        let i = i?;
+        if filter.matches(&deserialize(&table.try_read_slot(i)?)) {
+            matching.push(i);
+        }
+    }
+    Ok(matching)
+}
+
+/// The write-ahead log only records row writes, so changes that discard
+/// rows are made durable by flushing the table, which also empties the log.
+fn checkpoint(table: &mut Table, affected: usize) -> ExecuteResult {
+    match table.flush_dirty() {
+        Ok(()) => ExecuteResult::Success { affected },
+        Err(err) => ExecuteResult::Io(err),
+    }
+}
+
+pub fn execute_insert(table: &mut Table, row: &Row) -> ExecuteResult {
+    match table.insert_row(row.clone()) {
+        Ok(_) => ExecuteResult::Inserted { id: row.id },
+        Err(err) => err.into(),
+    }
+}
+
+/// Inserts `rows` in order, stopping at the first one that fails.
+pub fn execute_insert_batch(table: &mut Table, rows: &[Row]) -> ExecuteResult {
+    for (inserted, row) in rows.iter().enumerate() {
+        match execute_insert(table, row) {
+            ExecuteResult::Inserted { .. } => {}
+            cause => return ExecuteResult::BatchStopped { inserted, cause: Box::new(cause) },
+        }
+    }
+    ExecuteResult::Success { affected: rows.len() }
+}
+
+/// `insert or replace`: overwrites the row with the same id in place, or
+/// inserts it if the id is new.
+pub fn execute_upsert(table: &mut Table, row: &Row) -> ExecuteResult {
+    let index = match table.try_find_id(row.id) {
+        Ok(Some(index)) => index,
+        Ok(None) => return execute_insert(table, row),
+        Err(err) => return err.into(),
+    };
+    if let Err(err) = table.wal_append(index, row) {
+        return ExecuteResult::Io(err);
+    }
+    match table.try_row_slot(index) {
+        Ok(slot) => serialize(row, slot),
+        Err(err) => return err.into(),
+    }
+    ExecuteResult::Inserted { id: row.id }
+}
+
+/// `update <id> set ...`: overwrites the named columns of the row with `id`,
+/// keeping the others, and logs the new row to the write-ahead log.
+pub fn execute_update(table: &mut Table, id: u64, assignments: &[(Column, Option<String>)]) -> ExecuteResult {
+    let index = match table.try_find_id(id) {
+        Ok(Some(index)) => index,
+        Ok(None) => return ExecuteResult::Success { affected: 0 },
+        Err(err) => return err.into(),
+    };
+    let existing = match table.try_read_slot(index) {
+        Ok(slot) => deserialize(&slot),
+        Err(err) => return err.into(),
+    };
+    let mut username = existing.username_str().map(Cow::into_owned);
+    let mut email = existing.email_str().map(Cow::into_owned);
+    for (column, value) in assignments {
+        match column {
+            Column::Username => username = value.clone(),
+            Column::Email => email = value.clone(),
+            Column::Id => unreachable!("the parser rejects 'set id'"),
+        }
+    }
+    let row = Row::try_new_nullable(id, username, email).expect("the parser checks assigned lengths");
+    if let Err(err) = table.wal_append(index, &row) {
+        return ExecuteResult::Io(err);
+    }
+    match table.try_row_slot(index) {
+        Ok(slot) => serialize(&row, slot),
+        Err(err) => return err.into(),
+    }
+    ExecuteResult::Success { affected: 1 }
+}
+
+/// The rows a select would visit for `filter` and `order_by`, in order; they
+/// still need checking against `filter`. Without `order by` the table is
+/// walked lazily, so a caller that stops early reads no further rows.
+fn candidate_indices<'a>(
+    table: &'a Table,
+    filter: Option<&Filter>,
+    order_by: Option<OrderBy>,
+) -> Box<dyn Iterator<Item = Result<usize, DbError>> + 'a> {
+    let indices = match (filter, order_by) {
+        // only the indexed rows need reading; they are already in row order
+        (Some(Filter::UsernameEquals(username)), None) => table.try_find_username(username),
+        (_, None) => return Box::new(table.try_live_indices()),
+        (_, Some(order_by)) => select_order(table, order_by),
+    };
+    match indices {
+        Ok(indices) => Box::new(indices.into_iter().map(Ok)),
+        Err(err) => Box::new(std::iter::once(Err(err))),
+    }
+}
+
+/// Row indices in the order `order_by` emits them. Sorting needs every row's
+/// key, so the whole table is read; only the indices are reordered and the
+/// stored rows are left untouched.
+fn select_order(table: &Table, order_by: OrderBy) -> Result<Vec<usize>, DbError> {
+    let mut keyed: Vec<(u64, usize)> = Vec::new();
+    for i in table.try_live_indices() {
+        let i = i?;
+        keyed.push((deserialize_id(&table.try_read_slot(i)?), i));
+    }
+    match order_by.column {
+        Column::Id => {
+            keyed.sort_by_key(|&(id, _)| id);
+            if order_by.descending {
+                keyed.reverse();
+            }
+        }
+        Column::Username => {
+            let mut by_username = keyed
+                .into_iter()
+                .map(|(id, i)| Ok((deserialize(&table.try_read_slot(i)?).username_str().map(Cow::into_owned), id, i)))
+                .collect::<Result<Vec<(Option<String>, u64, usize)>, DbError>>()?;
+            // NULL sorts below every username; equal usernames stay in id order
+            // whichever way the usernames run
+            by_username.sort_by(|(a, a_id, _), (b, b_id, _)| {
+                let order = if order_by.descending { b.cmp(a) } else { a.cmp(b) };
+                order.then(a_id.cmp(b_id))
+            });
+            return Ok(by_username.into_iter().map(|(_, _, i)| i).collect());
+        }
+        Column::Email => unreachable!("ORDER BY email is not supported"),
+    }
+    Ok(keyed.into_iter().map(|(_, i)| i).collect())
+}
+
+pub fn execute_select<W: Write>(table: &Table, select: &Select, settings: &SelectSettings, writer: &mut W) -> ExecuteResult {
+    match write_select(table, select, settings, writer) {
+        Ok(()) => ExecuteResult::Success { affected: 0 },
+        Err(err) => err.into(),
+    }
+}
+
+/// The body of `execute_select`; a page that fails to load stops the
+/// output where it is.
+fn write_select<W: Write>(
+    table: &Table,
+    select: &Select,
+    settings: &SelectSettings,
+    writer: &mut W,
+) -> Result<(), DbError> {
+    if select.count {
+        // matches are counted rather than written
+        let mut count = 0;
+        for i in candidate_indices(table, select.filter.as_ref(), select.order_by) {
+            let row = deserialize(&table.try_read_slot(i?)?);
+            if select.filter.as_ref().is_none_or(|filter| filter.matches(&row)) {
+                count += 1;
+            }
+        }
+        writeln!(writer, "{}", count).unwrap();
+        return Ok(());
+    }
+    if let Some(aggregate) = select.aggregate {
+        let mut ids: Vec<u64> = Vec::new();
+        match &select.filter {
+            // without a filter only the ids need decoding
+            None => {
+                for i in table.try_live_indices() {
+                    ids.push(deserialize_id(&table.try_read_slot(i?)?));
+                }
+            }
+            Some(filter) => {
+                for i in candidate_indices(table, Some(filter), None) {
+                    let row = deserialize(&table.try_read_slot(i?)?);
+                    if filter.matches(&row) {
+                        ids.push(row.id);
+                    }
+                }
+            }
+        }
+        if ids.is_empty() {
+            writeln!(writer, "No rows.").unwrap();
+            return Ok(());
+        }
+        match aggregate {
+            Aggregate::Min => writeln!(writer, "{}", ids.iter().min().unwrap()).unwrap(),
+            Aggregate::Max => writeln!(writer, "{}", ids.iter().max().unwrap()).unwrap(),
+            Aggregate::Avg => {
+                let sum: u128 = ids.iter().map(|&id| id as u128).sum();
+                writeln!(writer, "{:.2}", sum as f64 / ids.len() as f64).unwrap()
+            }
+        }
+        return Ok(());
+    }
+    let candidates = match select.row_end {
+        Some(row_end) => {
+            // stops at the first matching row from that end of the table
+            let matching = |i: usize| -> Result<Option<usize>, DbError> {
+                let row = deserialize(&table.try_read_slot(i)?);
+                let matches = !row.is_deleted() && select.filter.as_ref().is_none_or(|filter| filter.matches(&row));
+                Ok(matches.then_some(i))
+            };
+            let found = match row_end {
+                RowEnd::First => (0..table.num_rows).map(matching).find_map(Result::transpose),
+                RowEnd::Last => (0..table.num_rows).rev().map(matching).find_map(Result::transpose),
+            };
+            let Some(index) = found.transpose()? else {
+                writeln!(writer, "No rows.").unwrap();
+                return Ok(());
+            };
+            Box::new(std::iter::once(Ok(index)))
+        }
+        None => candidate_indices(table, select.filter.as_ref(), select.order_by),
+    };
+    let columns: &[Column] = if select.columns.is_empty() { &Column::ALL } else { &select.columns };
+    let mode = if settings.raw { OutputMode::List } else { settings.mode };
+    let separator = settings.separator.as_deref().unwrap_or(" ");
+    if mode == OutputMode::Json {
+        write!(writer, "[").unwrap();
+    }
+    // column widths depend on every row, so the table is buffered
+    let mut table_rows: Vec<Vec<String>> = Vec::new();
+    let mut written = 0;
+    let mut seen_ids = HashSet::new();
+    let limit = select.limit.or(settings.default_limit);
+    let mut emitted = 0;
+    let mut scanned = 0;
+    let mut scan_limit_reached = false;
+    for i in candidates {
+        if settings.scan_limit.is_some_and(|scan_limit| scanned == scan_limit) {
+            scan_limit_reached = true;
+            break;
+        }
+        scanned += 1;
+        let row = deserialize(&table.try_read_slot(i?)?);
+        if select.filter.as_ref().is_some_and(|filter| !filter.matches(&row)) {
+            continue;
+        }
+        if select.distinct && !seen_ids.insert(row.id) {
+            continue;
+        }
+        if limit.is_some_and(|limit| emitted == limit) {
+            break;
+        }
+        emitted += 1;
+        match mode {
+            OutputMode::List if settings.raw => row.write_raw_columns(columns, separator, writer),
+            OutputMode::List => row.write_columns(columns, separator, writer),
+            OutputMode::Column => {
+                table_rows.push(columns.iter().map(|&column| display_nullable(row.value(column)).into_owned()).collect());
+                continue;
+            }
+            OutputMode::Csv => writeln!(writer, "{}", row.csv_line(columns)).unwrap(),
+            OutputMode::Json => {
+                if written > 0 {
+                    write!(writer, ",").unwrap();
+                }
+                write!(writer, "{}", row.json_object(columns)).unwrap();
+            }
+        }
+        written += 1;
+        if written % SELECT_FLUSH_INTERVAL == 0 {
+            writer.flush().unwrap();
+        }
+    }
+    if mode == OutputMode::Json {
+        writeln!(writer, "]").unwrap();
+    }
+    if mode == OutputMode::Column {
+        write_aligned(columns, &table_rows, &settings.widths, writer);
+    }
+    if scan_limit_reached {
+        writeln!(writer, "scan limit reached after {} rows", scanned).unwrap();
+    }
+    writer.flush().unwrap();
+    Ok(())
+}
+
+/// Writes a header row and `rows` with each column padded to its widest
+/// value, or to its fixed width from `fixed` with longer values cut short
+/// by an ellipsis. The last column is left unpadded to avoid trailing spaces.
+fn write_aligned<W: Write>(columns: &[Column], rows: &[Vec<String>], fixed: &[Option<usize>; 3], writer: &mut W) {
+    let header: Vec<String> = columns.iter().map(|column| column.name().to_string()).collect();
+    let fixed: Vec<Option<usize>> = columns.iter().map(|&column| fixed[column as usize]).collect();
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    for (width, fixed) in widths.iter_mut().zip(&fixed) {
+        *width = fixed.unwrap_or(*width);
+    }
+    for line in std::iter::once(&header).chain(rows) {
+        let last = line.len() - 1;
+        let cells: Vec<String> = line
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let value = match fixed[i] {
+                    Some(width) => truncate_with_ellipsis(value, width),
+                    None => value.clone(),
+                };
+                if i == last { value } else { format!("{:<width$}", value, width = widths[i]) }
+            })
+            .collect();
+        writeln!(writer, "{}", cells.join(" | ")).unwrap();
+    }
+}
+
+/// `value` cut to `width` characters, the last of them an ellipsis, if it
+/// does not fit.
+fn truncate_with_ellipsis(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        return value.to_string();
+    }
+    let mut truncated: String = value.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('\u{2026}');
+    truncated
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub fn execute_select_json<W: Write>(table: &Table, writer: &mut W) -> ExecuteResult {
+    write!(writer, "[").unwrap();
+    for (n, row) in live_rows_io(table).enumerate() {
+        let row = match row {
+            Ok(row) => row,
+            Err(err) => return ExecuteResult::Io(err),
+        };
+        if n > 0 {
+            write!(writer, ",").unwrap();
+        }
+        write!(writer, "{}", row.json_object(&Column::ALL)).unwrap();
+    }
+    writeln!(writer, "]").unwrap();
+    ExecuteResult::Success { affected: 0 }
+}
+
+/// The live rows in order, for writers that report failures as `io::Error`.
+fn live_rows_io(table: &Table) -> impl Iterator<Item = io::Result<Row>> + '_ {
+    table.try_live_indices().map(move |i| {
+        i.and_then(|i| table.try_read_slot(i)).map(|slot| deserialize(&slot)).map_err(into_io_error)
+    })
+}
+
+/// A failed row read as an `io::Error`: a page that fails its checksum
+/// comes through as `InvalidData`.
+fn into_io_error(err: DbError) -> io::Error {
+    match err {
+        DbError::Io(err) | DbError::Read(ReadError::Io(err)) => err,
+        DbError::Read(err) => io::Error::new(io::ErrorKind::InvalidData, err),
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn export_csv<W: Write>(table: &Table, writer: &mut W) -> io::Result<()> {
+    for row in live_rows_io(table) {
+        let row = row?;
+        writeln!(writer, "{}", row.csv_line(&Column::ALL))?;
+    }
+    Ok(())
+}
+
+/// Writes one `insert` statement per row, in the form `parse`
+/// parses, so the output can be fed back into a REPL to rebuild the table.
+/// Values containing line breaks will not survive the line-based REPL.
+pub fn dump<W: Write>(table: &Table, writer: &mut W) -> io::Result<()> {
+    for row in live_rows_io(table) {
+        let row = row?;
+        writeln!(
+            writer,
+            "insert {} {} {}",
+            row.id,
+            row.username_str().map_or("NULL".to_string(), |value| dump_field(&value)),
+            row.email_str().map_or("NULL".to_string(), |value| dump_field(&value))
+        )?;
+    }
+    Ok(())
+}
+
+// Quoted when the tokenizer would otherwise split it, read it as NULL or
+// drop it entirely.
+fn dump_field(field: &str) -> String {
+    let needs_quotes = field.is_empty()
+        || is_keyword(field, "null")
+        || field.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\');
+    if needs_quotes {
+        format!("\"{}\"", field.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Malformed { line: usize, reason: String },
+    StringTooLong { line: usize },
+    DuplicateKey { line: usize, id: u64 },
+    TableFull { line: usize },
+    ReadOnly,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "{}", err),
+            ImportError::Malformed { line, reason } => write!(f, "line {}: {}", line, reason),
+            ImportError::StringTooLong { line } => write!(f, "line {}: string is too long", line),
+            ImportError::DuplicateKey { line, id } => write!(f, "line {}: duplicate key {}", line, id),
+            ImportError::TableFull { line } => write!(f, "line {}: table full", line),
+            ImportError::ReadOnly => write!(f, "database is read-only"),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+fn parse_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+/// Reads `id,username,email` lines and inserts each row through
+/// `execute_insert`, stopping at the first line that fails.
+pub fn import_csv<R: io::BufRead>(table: &mut Table, reader: &mut R) -> Result<usize, ImportError> {
+    let mut count = 0;
+    for (index, line) in io::BufRead::lines(&mut *reader).enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(ImportError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let malformed = |reason: &str| ImportError::Malformed { line: line_number, reason: reason.to_string() };
+        let fields = parse_csv_line(&line).ok_or_else(|| malformed("unterminated quote"))?;
+        let [id, username, email] = <[String; 3]>::try_from(fields)
+            .map_err(|fields| malformed(&format!("expected 3 fields, found {}", fields.len())))?;
+        let id = id.trim().parse::<u64>().map_err(|_| malformed(&format!("invalid id '{}'", id)))?;
+        let row = Row::try_new(id, username, email).map_err(|_| ImportError::StringTooLong { line: line_number })?;
+        import_row(table, &row, line_number)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Reads one JSON object per line, in the shape `.mode json` writes each
+/// row: `{"id":1,"username":"alice","email":"alice@example.com"}`, with
+/// `null` for a NULL column. Each row goes through `execute_insert`, and
+/// the first line that fails stops the import.
+pub fn import_jsonl<R: io::BufRead>(table: &mut Table, reader: &mut R) -> Result<usize, ImportError> {
+    let mut count = 0;
+    for (index, line) in io::BufRead::lines(&mut *reader).enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(ImportError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let malformed = |reason: String| ImportError::Malformed { line: line_number, reason };
+        let mut fields: [Option<JsonValue>; 3] = [None, None, None];
+        for (key, value) in jsonl::parse_object(&line).map_err(malformed)? {
+            let Some(position) = Column::ALL.iter().position(|column| column.name() == key) else {
+                return Err(malformed(format!("unknown field '{}'", key)));
+            };
+            if fields[position].replace(value).is_some() {
+                return Err(malformed(format!("duplicate field '{}'", key)));
+            }
+        }
+        let [id, username, email] = fields;
+        let missing = |column: Column| malformed(format!("missing field '{}'", column.name()));
+        let id = match id.ok_or_else(|| missing(Column::Id))? {
+            JsonValue::Number(id) => id.parse::<u64>().map_err(|_| malformed(format!("invalid id '{}'", id)))?,
+            _ => return Err(malformed("id must be a number".to_string())),
+        };
+        let text = |value: Option<JsonValue>, column: Column| match value.ok_or_else(|| missing(column))? {
+            JsonValue::Null => Ok(None),
+            JsonValue::String(text) => Ok(Some(text)),
+            JsonValue::Number(_) => Err(malformed(format!("{} must be a string or null", column.name()))),
+        };
+        let username = text(username, Column::Username)?;
+        let email = text(email, Column::Email)?;
+        let row = Row::try_new_nullable(id, username, email)
+            .map_err(|_| ImportError::StringTooLong { line: line_number })?;
+        import_row(table, &row, line_number)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn import_row(table: &mut Table, row: &Row, line: usize) -> Result<(), ImportError> {
+    match execute_insert(table, row) {
+        ExecuteResult::Inserted { .. } => Ok(()),
+        ExecuteResult::TableFull => Err(ImportError::TableFull { line }),
+        ExecuteResult::DuplicateKey => Err(ImportError::DuplicateKey { line, id: row.id }),
+        ExecuteResult::ReadOnly => Err(ImportError::ReadOnly),
+        ExecuteResult::Io(err) => Err(ImportError::Io(err)),
+        ExecuteResult::Read(err) => Err(ImportError::Io(io::Error::new(io::ErrorKind::InvalidData, err))),
+        ExecuteResult::Success { .. }
+        | ExecuteResult::TransactionActive
+        | ExecuteResult::NoTransaction
+        | ExecuteResult::BatchStopped { .. } => {
+            unreachable!("execute_insert inserts a single row outside any transaction handling")
+        }
+    }
+}
+
+fn progress_message(statement_type: &StatementType) -> &'static str {
+    match statement_type {
+        StatementType::Insert { .. } => "inserting...",
+        StatementType::Select(_) => "selecting...",
+        StatementType::Delete { .. } => "deleting...",
+        StatementType::Update { .. } => "updating...",
+        StatementType::Begin => "beginning transaction...",
+        StatementType::Commit => "committing...",
+        StatementType::Rollback => "rolling back...",
+    }
+}
+
+fn changed_rows(result: &ExecuteResult) -> usize {
+    match result {
+        ExecuteResult::Success { affected } => *affected,
+        ExecuteResult::Inserted { .. } => 1,
+        ExecuteResult::BatchStopped { inserted, .. } => *inserted,
+        _ => 0,
+    }
+}
+
+fn affected_message(statement_type: &StatementType, affected: usize) -> Option<String> {
+    let verb = match statement_type {
+        StatementType::Insert { .. } => "inserted",
+        StatementType::Delete { .. } => "deleted",
+        StatementType::Update { .. } => "updated",
+        StatementType::Select(_) | StatementType::Begin | StatementType::Commit | StatementType::Rollback => return None,
+    };
+    let noun = if affected == 1 { "row" } else { "rows" };
+    Some(format!("{} {} {}", affected, noun, verb))
+}
+
+fn write_execute_result<W: Write>(
+    writer: &mut W,
+    state: &ReplState,
+    table: &Table,
+    statement_type: &StatementType,
+    row: Option<&Row>,
+    result: ExecuteResult,
+) {
+    match result {
+        ExecuteResult::Success { affected } => {
+            if let Some(message) = affected_message(statement_type, affected) {
+                writeln!(writer, "{}", message).unwrap();
+            }
+        }
+        ExecuteResult::Inserted { id } => {
+            writeln!(writer, "{}", affected_message(statement_type, 1).unwrap()).unwrap();
+            if state.verbose {
+                writeln!(writer, "Inserted id {}", id).unwrap();
+            }
+        }
+        ExecuteResult::TableFull => writeln!(
+            writer,
+            "Row not inserted, table full ({}/{} rows) '{}'",
+            table.num_rows,
+            table.max_rows(),
+            row.expect("Row not initialized panic")
+        )
+        .unwrap(),
+        ExecuteResult::DuplicateKey => writeln!(writer, "{}", DbError::DuplicateKey).unwrap(),
+        ExecuteResult::TransactionActive => writeln!(writer, "Error: a transaction is already active.").unwrap(),
+        ExecuteResult::NoTransaction => writeln!(writer, "Error: no transaction is active.").unwrap(),
+        ExecuteResult::ReadOnly => writeln!(writer, "{}", DbError::ReadOnly).unwrap(),
+        ExecuteResult::Io(err) => writeln!(writer, "{}", DbError::Io(err)).unwrap(),
+        ExecuteResult::Read(err) => writeln!(writer, "{}", DbError::Read(err)).unwrap(),
+        ExecuteResult::BatchStopped { inserted, cause } => {
+            let rows = match statement_type {
+                StatementType::Insert { rows, .. } => rows.as_slice(),
+                _ => &[],
+            };
+            writeln!(writer, "{} of {} rows inserted", inserted, rows.len()).unwrap();
+            write_execute_result(writer, state, table, statement_type, rows.get(inserted), *cause);
+        }
+    }
+}
+
+/// Builds the table for the command line `rsql [path]`: the database file at
+/// `path` if one is given, otherwise a fresh in-memory table.
+pub fn open_table_from_args<I>(args: I) -> Result<Table, OpenError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    match args.into_iter().nth(1) {
+        Some(path) => Table::open(path),
+        None => Ok(Table::new()),
+    }
+}
+
+pub fn run_repl<R: io::BufRead, W: Write>(table: &mut Table, reader: &mut R, writer: &mut W) {
+    let mut input_buffer = String::new();
+    let mut state = ReplState::default();
+
+    loop {
+        print_prompt(writer);
+        input_buffer.clear();
+
+        match reader.read_line(&mut input_buffer) {
+            Ok(0) => {
+                // EOF (Ctrl-D or the end of piped input) ends the session.
+                writeln!(writer).unwrap();
+                break;
+            }
+            Ok(_) => {}
+            Err(_) => {
+                writeln!(writer, "Error reading input").unwrap();
+                break;
+            }
+        }
+
+        if let LineOutcome::Exit = run_line_paged(&input_buffer, table, &mut state, writer, Some(&mut *reader)) {
+            break;
+        }
+    }
+}
+
+/// What `run_line` made of one line of input.
+#[derive(Debug, PartialEq)]
+pub enum LineOutcome {
+    Ok,
+    Failed,
+    Exit,
+}
+
+/// Runs one line of input, a meta command or a statement, as typed at the
+/// prompt. `run_repl` is a loop over this; interactive shells that read
+/// lines their own way call it directly with a long-lived `ReplState`.
+/// Surrounding whitespace is ignored, and a blank line does nothing.
+pub fn run_line<W: Write>(input: &str, table: &mut Table, state: &mut ReplState, writer: &mut W) -> LineOutcome {
+    run_line_paged(input, table, state, writer, None)
+}
+
+/// `run_line` for a caller that can also hand over its input, so that select
+/// results can pause between screenfuls while `.pager` is on.
+fn run_line_paged<W: Write>(
+    input: &str,
+    table: &mut Table,
+    state: &mut ReplState,
+    writer: &mut W,
+    more: Option<&mut dyn io::BufRead>,
+) -> LineOutcome {
+    let input = input.trim();
+    if input.is_empty() {
+        return LineOutcome::Ok;
+    }
+
+    if state.echo {
+        writeln!(writer, "{}", input).unwrap();
+    }
+
+    if input.starts_with('.') {
+        return match do_meta_command(input, table, state, writer) {
+            MetaCommandResult::Exit => LineOutcome::Exit,
+            MetaCommandResult::Unrecognized => LineOutcome::Failed,
+            MetaCommandResult::Success => LineOutcome::Ok,
+        };
+    }
+
+    let mut statement = Statement::default();
+
+    match prepare_statement(input, &mut statement) {
+        PrepareResult::Success => {
+            if state.verbose {
+                writeln!(writer, "{}", progress_message(&statement.statement_type)).unwrap();
+            }
+            let settings = state.select_settings();
+            let paging = match (state.pager, more) {
+                (Some(lines), Some(reader)) if state.output.is_none() => Some((lines, reader)),
+                _ => None,
+            };
+            let start = Instant::now();
+            let mut paged = Vec::new();
+            let result = match &mut state.output {
+                Some(output) => execute_statement(table, &statement, &settings, output),
+                None if paging.is_some() => execute_statement(table, &statement, &settings, &mut paged),
+                None => execute_statement(table, &statement, &settings, writer),
+            };
+            let elapsed = start.elapsed();
+            if let Some((lines, reader)) = paging {
+                write_paged(&paged, lines, reader, writer);
+            }
+            let outcome = match result {
+                ExecuteResult::Success { .. } | ExecuteResult::Inserted { .. } => LineOutcome::Ok,
+                _ => LineOutcome::Failed,
+            };
+            // an explained write changes nothing, so it leaves `.changes` alone
+            // and prints no row count
+            if !statement.explain {
+                if statement.statement_type.is_write() {
+                    state.changes = changed_rows(&result);
+                }
+                let row = match &statement.statement_type {
+                    StatementType::Insert { rows, .. } => rows.first(),
+                    _ => None,
+                };
+                write_execute_result(writer, state, table, &statement.statement_type, row, result);
+            }
+            if state.verbose {
+                writeln!(writer, "rows: {}", table.num_rows).unwrap();
+            }
+            if state.timer {
+                writeln!(writer, "Run Time: real {:.6}", elapsed.as_secs_f64()).unwrap();
+            }
+            return outcome;
+        }
+        PrepareResult::SyntaxError(None) => writeln!(writer, "{}", DbError::Parse(input.to_string())).unwrap(),
+        PrepareResult::SyntaxError(Some(reason)) => writeln!(writer, "{}: {}", DbError::Parse(input.to_string()), reason).unwrap(),
+        PrepareResult::SyntaxErrorAt { column, expected } => {
+            writeln!(writer, "Syntax error at column {}: expected {}", column, expected).unwrap()
+        }
+        PrepareResult::MissingFields { column, parsed, missing } => {
+            writeln!(writer, "Syntax error at column {}: {}", column, describe_missing_fields(&parsed, &missing)).unwrap()
+        }
+        PrepareResult::StringTooLong => writeln!(writer, "{}", DbError::StringTooLong).unwrap(),
+        PrepareResult::InvalidEmail(email) => writeln!(writer, "Invalid email: {}", email).unwrap(),
+        PrepareResult::Unrecognized => writeln!(writer, "Unrecognized keyword at start of '{}'", input).unwrap(),
+    }
+    LineOutcome::Failed
+}
+
+/// Writes `output` `lines` lines at a time, waiting after each screenful for
+/// a line of input: anything continues, while `q` or the end of input drops
+/// the rest.
+fn write_paged<W: Write>(output: &[u8], lines: usize, reader: &mut dyn io::BufRead, writer: &mut W) {
+    let output: Vec<&[u8]> = output.split_inclusive(|&byte| byte == b'\n').collect();
+    for (page, chunk) in output.chunks(lines).enumerate() {
+        if page > 0 {
+            write!(writer, "-- more --").unwrap();
+            writer.flush().unwrap();
+            let mut answer = String::new();
+            match reader.read_line(&mut answer) {
+                Ok(0) | Err(_) => {
+                    writeln!(writer).unwrap();
+                    return;
+                }
+                Ok(_) if answer.trim().eq_ignore_ascii_case("q") => return,
+                Ok(_) => {}
+            }
+        }
+        for line in chunk {
+            writer.write_all(line).unwrap();
+        }
+    }
+}
+
+/// How deeply `.read` scripts may nest, so a script that reads itself stops
+/// with an error rather than overflowing the stack.
+const MAX_READ_DEPTH: usize = 16;
+
+/// Runs each line of the script at `path` as if it were typed at the prompt.
+/// A failing line is reported with its line number, and `.bail on` stops the
+/// script there.
+fn read_script<W: Write>(path: &str, table: &mut Table, state: &mut ReplState, writer: &mut W) -> MetaCommandResult {
+    if state.read_depth >= MAX_READ_DEPTH {
+        writeln!(writer, "Error reading '{}': scripts nested more than {} deep", path, MAX_READ_DEPTH).unwrap();
+        return MetaCommandResult::Success;
+    }
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            writeln!(writer, "Error reading '{}': {}", path, err).unwrap();
+            return MetaCommandResult::Success;
+        }
+    };
+    state.read_depth += 1;
+    let mut result = MetaCommandResult::Success;
+    for (number, line) in io::BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                writeln!(writer, "Error reading '{}': {}", path, err).unwrap();
+                break;
+            }
+        };
+        // buffered so a failure can be prefixed with where it happened
+        let mut output = Vec::new();
+        let outcome = run_line(line.trim(), table, state, &mut output);
+        if let LineOutcome::Failed = outcome {
+            write!(writer, "line {}: ", number + 1).unwrap();
+        }
+        writer.write_all(&output).unwrap();
+        match outcome {
+            LineOutcome::Exit => {
+                result = MetaCommandResult::Exit;
+                break;
+            }
+            LineOutcome::Failed if state.bail => break,
+            LineOutcome::Ok | LineOutcome::Failed => {}
+        }
+    }
+    state.read_depth -= 1;
+    result
+}
+
+// unit tests
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    /// Rows with arbitrary column bytes, built from byte vectors no longer
+    /// than each column so shrinking converges on short, minimal values.
+    fn arb_row() -> impl Strategy<Value = Row> {
+        (
+            any::<u64>(),
+            prop::collection::vec(any::<u8>(), 0..=COLUMN_USERNAME_SIZE),
+            prop::collection::vec(any::<u8>(), 0..=COLUMN_EMAIL_SIZE),
+            any::<u8>(),
+        )
+            .prop_map(|(id, username_bytes, email_bytes, nulls)| {
+                let mut row = Row { id, username: [0; COLUMN_USERNAME_SIZE], email: [0; COLUMN_EMAIL_SIZE], nulls };
+                row.username[..username_bytes.len()].copy_from_slice(&username_bytes);
+                row.email[..email_bytes.len()].copy_from_slice(&email_bytes);
+                row
+            })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(4096))]
+
+        #[test]
+        fn prop_serialize_deserialize_round_trip(row in arb_row()) {
+            let mut buffer = [0u8; ROW_SIZE];
+            serialize(&row, &mut buffer);
+            prop_assert_eq!(deserialize(&buffer), row);
+        }
+
+        // fuzz harness: arbitrary bytes, including the wrong length, must
+        // never panic and only decode when exactly one row long
+        #[test]
+        fn prop_try_deserialize_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..=2 * ROW_SIZE)) {
+            match try_deserialize(&bytes) {
+                Ok(row) => {
+                    prop_assert_eq!(bytes.len(), ROW_SIZE);
+                    prop_assert_eq!(&row.to_bytes()[..], &bytes[..]);
+                }
+                Err(err) => {
+                    prop_assert_ne!(bytes.len(), ROW_SIZE);
+                    prop_assert_eq!(err, DeserializeError::WrongLength(bytes.len()));
+                }
+            }
+        }
+    }
 
     #[test]
     fn test_row_creation() {
@@ -298,9 +3122,49 @@ mod tests {
         assert_eq!(deserialized_row, original_row);
     }
 
+    fn texts(input: &str) -> Vec<String> {
+        tokenize(input).unwrap().into_iter().map(|token| token.text).collect()
+    }
+
+    #[test]
+    fn test_tokenize_quotes() {
+        assert_eq!(texts(r#"insert 1 "John Doe" a@b.com"#), vec!["insert", "1", "John Doe", "a@b.com"]);
+        assert_eq!(texts(r#""say \"hi\"""#), vec![r#"say "hi""#]);
+        assert_eq!(tokenize(r#"insert 1 "John"#), Err(TokenizeError::UnterminatedQuote));
+
+        let tokens = tokenize(r#"x "a b" "null""#).unwrap();
+        assert_eq!((tokens[1].start, tokens[1].end, tokens[1].quoted), (2, 7, true));
+        assert_eq!((tokens[2].text.as_str(), tokens[2].quoted), ("null", true));
+    }
+
+    #[test]
+    fn test_tokenize_separators_and_positions() {
+        assert_eq!(texts("select  id,\t username"), vec!["select", "id,", "username"]);
+        assert_eq!(texts(""), Vec::<String>::new());
+        assert_eq!(texts(" \t "), Vec::<String>::new());
+
+        let tokens = tokenize("  insert 1  ").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!((tokens[0].start, tokens[0].end), (2, 8));
+        assert_eq!((tokens[1].start, tokens[1].end), (9, 10));
+        assert!(!tokens[0].quoted);
+    }
+
+    #[test]
+    fn test_like_match() {
+        assert!(like_match("%@example.com", "alice@example.com"));
+        assert!(like_match("al%", "alice@example.com"));
+        assert!(like_match("a_ice%", "alice@example.com"));
+        assert!(like_match("%", ""));
+        assert!(like_match("%e%e%", "alice@example.com"));
+        assert!(!like_match("%@example.org", "alice@example.com"));
+        assert!(!like_match("_", ""));
+        assert!(!like_match("alice", "alice@example.com"));
+    }
+
     #[test]
     fn test_row_layout() {
-        assert_eq!(std::mem::size_of::<Row>(), 1 + 4 + 32 + 255); // 291 bytes
-        assert_eq!(std::mem::align_of::<Row>(), 4); // u32 alignment
+        assert_eq!(std::mem::size_of::<Row>(), 8 + 32 + 255 + 1); // 296 bytes, no padding
+        assert_eq!(std::mem::align_of::<Row>(), 8); // u64 alignment
     }
 }
@@ -1,11 +1,75 @@
 // src/main.rs
 
-use std::io;
-use rsql::{Table, run_repl};
+use std::{env, io, process};
+use rsql::{open_table_from_args, run_repl, Table};
 
 fn main() {
-    let mut table = Table::new();
+    let mut table = match open_table_from_args(env::args_os()) {
+        Ok(table) => table,
+        Err(err) => {
+            eprintln!("Error opening database: {}", err);
+            process::exit(1);
+        }
+    };
+    if let Some(truncated) = table.truncated_page() {
+        eprintln!("Warning: {}", truncated);
+    }
+    run_shell(&mut table);
+    // a transaction still open at exit was never committed
+    if let Err(err) = table.rollback_transaction() {
+        eprintln!("Error rolling back transaction: {}", err);
+    }
+    if let Err(err) = table.flush_dirty() {
+        eprintln!("Error saving database: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "line-editor"))]
+fn run_shell(table: &mut Table) {
     let mut stdin = io::stdin().lock();
-    let mut stdout = io::stdout();
-    run_repl(&mut table, &mut stdin, &mut stdout);
+    let mut stdout = io::stdout().lock();
+    run_repl(table, &mut stdin, &mut stdout);
+}
+
+/// Reads lines through an editor with history when attached to a terminal;
+/// piped input still goes through `run_repl`.
+#[cfg(feature = "line-editor")]
+fn run_shell(table: &mut Table) {
+    use std::io::IsTerminal;
+    use rsql::{run_line, LineOutcome, ReplState};
+    use rustyline::{error::ReadlineError, DefaultEditor};
+
+    let mut stdout = io::stdout().lock();
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) if io::stdin().is_terminal() => editor,
+        _ => return run_repl(table, &mut io::stdin().lock(), &mut stdout),
+    };
+    let history = env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".rsql_history"));
+    if let Some(history) = &history {
+        // a missing history file just means a first run
+        let _ = editor.load_history(history);
+    }
+    let mut state = ReplState::default();
+    loop {
+        let line = match editor.readline("rsql > ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error reading input: {}", err);
+                break;
+            }
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+        if run_line(&line, table, &mut state, &mut stdout) == LineOutcome::Exit {
+            break;
+        }
+    }
+    if let Some(history) = &history {
+        if let Err(err) = editor.save_history(history) {
+            eprintln!("Error saving history: {}", err);
+        }
+    }
 }
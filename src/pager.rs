@@ -0,0 +1,661 @@
+// src/pager.rs
+
+use std::{
+    error::Error,
+    ffi::OsString,
+    fmt,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::MutexGuard,
+};
+
+use crate::{wal, Row, Table, TableConfig, PAGE_CHECKSUM_SIZE, PAGE_SIZE, ROW_SIZE};
+
+pub const FILE_MAGIC: [u8; 4] = *b"RSQL";
+pub const FORMAT_VERSION: u16 = 5;
+// magic + version + page size + row size + num_rows + next_id
+pub const HEADER_SIZE: usize = 4 + 2 + 4 + 4 + 8 + 8;
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// CRC-32 (IEEE), as used by zlib and PNG.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    ChecksumMismatch { page: usize },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "{}", err),
+            ReadError::ChecksumMismatch { page } => write!(f, "checksum mismatch on page {}", page),
+        }
+    }
+}
+
+impl Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum OpenError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    LayoutMismatch { file_page_size: usize, expected: usize },
+    RowSizeMismatch { file_row_size: usize, expected: usize },
+    Read(ReadError),
+    /// Another open table holds a conflicting lock on the file.
+    Locked(PathBuf),
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::Io(err) => write!(f, "{}", err),
+            OpenError::BadMagic => write!(f, "not an rsql database file"),
+            OpenError::UnsupportedVersion(version) => {
+                write!(f, "unsupported file format version {} (expected {})", version, FORMAT_VERSION)
+            }
+            OpenError::LayoutMismatch { file_page_size, expected } => {
+                write!(f, "file uses {}-byte pages, expected {}", file_page_size, expected)
+            }
+            OpenError::RowSizeMismatch { file_row_size, expected } => {
+                write!(f, "file uses {}-byte rows, expected {}", file_row_size, expected)
+            }
+            OpenError::Read(err) => write!(f, "{}", err),
+            OpenError::Locked(path) => write!(f, "{}: database is locked", path.display()),
+        }
+    }
+}
+
+impl Error for OpenError {}
+
+impl From<io::Error> for OpenError {
+    fn from(err: io::Error) -> Self {
+        OpenError::Io(err)
+    }
+}
+
+impl From<ReadError> for OpenError {
+    fn from(err: ReadError) -> Self {
+        OpenError::Read(err)
+    }
+}
+
+/// A final page that `open` found cut short, as a crash partway through
+/// extending the file can leave it. The rows wholly inside the bytes that
+/// remain are kept; the rest are dropped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TruncatedPage {
+    pub page: usize,
+    /// Bytes of the page present in the file.
+    pub bytes: usize,
+    pub rows_recovered: usize,
+    /// The row count the file header promised.
+    pub rows_expected: usize,
+}
+
+impl fmt::Display for TruncatedPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "page {} is truncated to {} bytes; recovered {} of {} rows",
+            self.page, self.bytes, self.rows_recovered, self.rows_expected
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FileHeader {
+    pub version: u16,
+    pub page_size: u32,
+    pub row_size: u32,
+    pub num_rows: u64,
+    pub next_id: u64,
+}
+
+impl FileHeader {
+    pub fn for_table(table: &Table) -> Self {
+        FileHeader {
+            version: FORMAT_VERSION,
+            page_size: table.config.page_size as u32,
+            row_size: ROW_SIZE as u32,
+            num_rows: table.num_rows as u64,
+            next_id: table.next_id,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&FILE_MAGIC);
+        bytes[4..6].copy_from_slice(&self.version.to_le_bytes());
+        bytes[6..10].copy_from_slice(&self.page_size.to_le_bytes());
+        bytes[10..14].copy_from_slice(&self.row_size.to_le_bytes());
+        bytes[14..22].copy_from_slice(&self.num_rows.to_le_bytes());
+        bytes[22..30].copy_from_slice(&self.next_id.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Result<Self, OpenError> {
+        if bytes[0..4] != FILE_MAGIC {
+            return Err(OpenError::BadMagic);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != FORMAT_VERSION {
+            return Err(OpenError::UnsupportedVersion(version));
+        }
+        Ok(FileHeader {
+            version,
+            page_size: u32::from_le_bytes(bytes[6..10].try_into().unwrap()),
+            row_size: u32::from_le_bytes(bytes[10..14].try_into().unwrap()),
+            num_rows: u64::from_le_bytes(bytes[14..22].try_into().unwrap()),
+            next_id: u64::from_le_bytes(bytes[22..30].try_into().unwrap()),
+        })
+    }
+}
+
+/// File offset of a page for the default `PAGE_SIZE`.
+pub fn page_offset(page_num: usize) -> u64 {
+    page_offset_for(page_num, PAGE_SIZE)
+}
+
+fn page_offset_for(page_num: usize, page_size: usize) -> u64 {
+    // in u64 so large page numbers cannot overflow a 32-bit usize
+    HEADER_SIZE as u64 + page_num as u64 * page_size as u64
+}
+
+/// Writes a page with its trailing checksum. The checksum region of the
+/// in-memory page is never used by rows, so it is filled in on the way out.
+fn write_page<W: Write>(writer: &mut W, page: &[u8]) -> io::Result<()> {
+    let data = &page[..page.len() - PAGE_CHECKSUM_SIZE];
+    writer.write_all(data)?;
+    writer.write_all(&crc32(data).to_le_bytes())
+}
+
+fn read_page<R: Read>(reader: &mut R, page_num: usize, page_size: usize) -> Result<Box<[u8]>, ReadError> {
+    let mut page = vec![0; page_size].into_boxed_slice();
+    reader.read_exact(&mut page)?;
+    let data_size = page_size - PAGE_CHECKSUM_SIZE;
+    let stored = u32::from_le_bytes(page[data_size..].try_into().unwrap());
+    if stored != crc32(&page[..data_size]) {
+        return Err(ReadError::ChecksumMismatch { page: page_num });
+    }
+    Ok(page)
+}
+
+/// Resident pages and their dirty flags. A `None` page is either not
+/// allocated yet or only on disk; `on_disk` tells which.
+#[derive(Clone, Debug)]
+pub(crate) struct PageCache {
+    pub(crate) pages: Vec<Option<Box<[u8]>>>,
+    pub(crate) dirty: Vec<bool>,
+    pub(crate) on_disk: Vec<bool>,
+    pub(crate) capacity: usize,
+    // `clock` value at each page's last use, for LRU eviction
+    last_used: Vec<u64>,
+    clock: u64,
+    // pages read from the backing file so far
+    pub(crate) loads: usize,
+}
+
+impl PageCache {
+    pub(crate) fn new(max_pages: usize, capacity: usize) -> Self {
+        PageCache {
+            pages: vec![None; max_pages],
+            dirty: vec![false; max_pages],
+            on_disk: vec![false; max_pages],
+            capacity,
+            last_used: vec![0; max_pages],
+            clock: 0,
+            loads: 0,
+        }
+    }
+
+    pub(crate) fn touch(&mut self, page_num: usize) {
+        self.clock += 1;
+        self.last_used[page_num] = self.clock;
+    }
+
+    pub(crate) fn resident(&self) -> usize {
+        self.pages.iter().filter(|page| page.is_some()).count()
+    }
+
+    fn least_recently_used(&self, keep: usize, clean_only: bool) -> Option<usize> {
+        (0..self.pages.len())
+            .filter(|&page_num| page_num != keep && self.pages[page_num].is_some())
+            .filter(|&page_num| !(clean_only && self.dirty[page_num]))
+            .min_by_key(|&page_num| self.last_used[page_num])
+    }
+}
+
+/// Names the file in an error from opening or creating it.
+pub(crate) fn with_path(err: io::Error, path: &Path) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+fn load_page(mut file: &File, page_num: usize, page_size: usize) -> Result<Box<[u8]>, ReadError> {
+    file.seek(SeekFrom::Start(page_offset_for(page_num, page_size)))?;
+    read_page(&mut file, page_num, page_size)
+}
+
+fn store_page(mut file: &File, page_num: usize, page: &[u8]) -> io::Result<()> {
+    file.seek(SeekFrom::Start(page_offset_for(page_num, page.len())))?;
+    write_page(&mut file, page)
+}
+
+/// Takes an advisory lock on a database file for as long as `file` stays
+/// open: exclusive for writers, shared for read-only opens. The locks are
+/// `flock` on Unix and `LockFileEx` on Windows; both are held per open
+/// file, so a second open in the same process conflicts just like one in
+/// another process. Advisory locks only bind other rsql processes, and may
+/// not be enforced at all on network filesystems.
+fn lock_file(file: &File, path: &Path, shared: bool) -> Result<(), OpenError> {
+    let result = if shared { file.try_lock_shared() } else { file.try_lock() };
+    match result {
+        Ok(()) => Ok(()),
+        Err(TryLockError::WouldBlock) => Err(OpenError::Locked(path.to_path_buf())),
+        Err(TryLockError::Error(err)) => Err(with_path(err, path).into()),
+    }
+}
+
+impl Table {
+    pub(crate) fn page_count(&self, num_rows: usize) -> usize {
+        num_rows.div_ceil(self.config.rows_per_page())
+    }
+
+    /// Releases the lock `open` took on the table's file, so the same path
+    /// can be opened again from this process; `relock_file` takes it back.
+    pub(crate) fn unlock_file(&self) -> io::Result<()> {
+        match &self.file {
+            Some(file) => file.unlock(),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn relock_file(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let result = if self.read_only { file.try_lock_shared() } else { file.try_lock() };
+        result.map_err(io::Error::from)
+    }
+
+    pub(crate) fn lock_cache(&self) -> MutexGuard<'_, PageCache> {
+        self.cache.lock().expect("page cache lock poisoned")
+    }
+
+    pub(crate) fn cache_mut(&mut self) -> &mut PageCache {
+        self.cache.get_mut().expect("page cache lock poisoned")
+    }
+
+    /// Reads `page_num` from the backing file into `cache` if it lives there
+    /// and is not resident yet, verifying its checksum on the way in. `open`
+    /// reads no pages, so this is where a corrupt page is first noticed.
+    pub(crate) fn load_page_into(&self, cache: &mut PageCache, page_num: usize) -> Result<(), ReadError> {
+        if cache.pages[page_num].is_some() || !cache.on_disk[page_num] {
+            return Ok(());
+        }
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        self.make_room(cache, page_num);
+        let page = load_page(file, page_num, self.config.page_size)?;
+        cache.pages[page_num] = Some(page);
+        cache.loads += 1;
+        Ok(())
+    }
+
+    /// `load_page_into` for callers that have no way to report a failure.
+    pub(crate) fn load_page_or_panic(&self, cache: &mut PageCache, page_num: usize) {
+        if let Err(err) = self.load_page_into(cache, page_num) {
+            panic!("failed to load page {}: {}", page_num, err);
+        }
+    }
+
+    /// Evicts least recently used pages until another one fits, writing
+    /// dirty ones back first. Tables without a file keep every page, since
+    /// an evicted page would have nowhere to be reloaded from. If a write
+    /// back fails the cache is left over capacity rather than losing the page.
+    pub(crate) fn make_room(&self, cache: &mut PageCache, keep: usize) {
+        // a read-only table cannot write back what it evicts
+        let Some(file) = self.file.as_ref().filter(|_| !self.read_only) else {
+            return;
+        };
+        // a transaction's changes must stay out of the file until it commits,
+        // so only clean pages can go; the cache grows past capacity otherwise
+        let clean_only = self.in_transaction();
+        while cache.resident() >= cache.capacity {
+            let Some(victim) = cache.least_recently_used(keep, clean_only) else {
+                return;
+            };
+            if cache.dirty[victim] {
+                let page = cache.pages[victim].as_deref().unwrap();
+                // a page that cannot be written back stays resident and
+                // dirty, so the next flush reports the failure
+                if store_page(file, victim, page).is_err() {
+                    return;
+                }
+                cache.dirty[victim] = false;
+                cache.on_disk[victim] = true;
+            }
+            cache.pages[victim] = None;
+        }
+    }
+
+    /// Opens (or creates) a database file, then replays any writes left in
+    /// its write-ahead log by a crash. Only the header is read up front; each
+    /// page's checksum is verified when the page is first loaded. A final
+    /// page cut short is padded with zeroes rather than refused; see
+    /// `truncated_page`.
+    /// Pages are not kept in memory until they are first used; the file
+    /// stays attached to the table to load them and for `flush_dirty`, and
+    /// is locked exclusively until the table is dropped; opening a file
+    /// another table holds fails with `OpenError::Locked`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Table, OpenError> {
+        Table::open_with_config(path, TableConfig::default())
+    }
+
+    /// `open`, with the page geometry and cache capacity from `config`.
+    pub fn open_with_config<P: AsRef<Path>>(path: P, config: TableConfig) -> Result<Table, OpenError> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|err| with_path(err, path))?;
+        lock_file(&file, path, false)?;
+        let mut table = Table::with_config(config);
+        if file.metadata()?.len() > 0 {
+            table.read_from(&file)?;
+        }
+        table.file = Some(file);
+        let mut wal = wal::open_wal(path)?;
+        table.replay_wal(&mut wal)?;
+        table.wal = Some(wal);
+        Ok(table)
+    }
+
+    /// Opens a database file for inspection only. Writes left in its
+    /// write-ahead log are applied in memory, but neither file is ever
+    /// written: statements that change rows fail with `ReadOnly`.
+    pub fn open_readonly<P: AsRef<Path>>(path: P) -> Result<Table, OpenError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| with_path(err, path))?;
+        lock_file(&file, path, true)?;
+        let mut table = Table::with_config(TableConfig::default());
+        table.read_only = true;
+        if file.metadata()?.len() > 0 {
+            table.read_from(&file)?;
+        }
+        table.file = Some(file);
+        let wal_path = wal::wal_path(path);
+        match File::open(&wal_path) {
+            Ok(mut wal) => {
+                table.replay_wal(&mut wal)?;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(with_path(err, &wal_path).into()),
+        }
+        Ok(table)
+    }
+
+    fn read_from(&mut self, mut file: &File) -> Result<(), OpenError> {
+        let mut header_bytes = [0; HEADER_SIZE];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header_bytes)?;
+        let header = FileHeader::from_bytes(&header_bytes)?;
+        if header.page_size as usize != self.config.page_size {
+            return Err(OpenError::LayoutMismatch {
+                file_page_size: header.page_size as usize,
+                expected: self.config.page_size,
+            });
+        }
+        if header.row_size as usize != ROW_SIZE {
+            return Err(OpenError::RowSizeMismatch {
+                file_row_size: header.row_size as usize,
+                expected: ROW_SIZE,
+            });
+        }
+        if header.num_rows > self.max_rows() as u64 {
+            return Err(OpenError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("file holds {} rows, more than the maximum of {}", header.num_rows, self.max_rows()),
+            )));
+        }
+        let num_rows = header.num_rows as usize;
+        let page_count = self.page_count(num_rows);
+        let page_size = self.config.page_size;
+        let file_len = file.metadata()?.len();
+        if page_count == 0 || file_len >= page_offset_for(page_count, page_size) {
+            self.cache_mut().on_disk[..page_count].fill(true);
+            self.num_rows = num_rows;
+            self.next_id = header.next_id;
+            return Ok(());
+        }
+        // only the final page may come up short; it has no checksum left to
+        // check, so its surviving bytes are read now and zero-padded
+        let last_page = page_count - 1;
+        let last_page_start = page_offset_for(last_page, page_size);
+        if file_len < last_page_start {
+            return Err(OpenError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("file holds {} bytes, too few for {} rows", file_len, num_rows),
+            )));
+        }
+        file.seek(SeekFrom::Start(last_page_start))?;
+        let mut page = Vec::with_capacity(page_size);
+        file.read_to_end(&mut page)?;
+        let bytes = page.len();
+        page.resize(page_size, 0);
+        let mut page = page.into_boxed_slice();
+        // keep only the rows that survived whole; a row cut in half is gone
+        let first_row = last_page * self.config.rows_per_page();
+        let intact = (bytes / ROW_SIZE).min(num_rows - first_row);
+        page[intact * ROW_SIZE..].fill(0);
+        let rows_recovered = first_row + intact;
+        let cache = self.cache_mut();
+        cache.on_disk[..last_page].fill(true);
+        if intact > 0 {
+            // the file no longer holds this page whole, so it stays resident
+            // and dirty until a flush writes it back out
+            cache.pages[last_page] = Some(page);
+            cache.dirty[last_page] = true;
+            cache.on_disk[last_page] = true;
+        }
+        self.num_rows = rows_recovered;
+        self.next_id = header.next_id;
+        self.truncated = Some(TruncatedPage { page: last_page, bytes, rows_recovered, rows_expected: num_rows });
+        Ok(())
+    }
+
+    /// The short final page `open` recovered from, if there was one.
+    pub fn truncated_page(&self) -> Option<&TruncatedPage> {
+        self.truncated.as_ref()
+    }
+
+    /// Writes the header followed by every page holding rows.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&FileHeader::for_table(self).to_bytes())?;
+        let empty = vec![0; self.config.page_size];
+        for page_num in 0..self.page_count(self.num_rows) {
+            let mut cache = self.lock_cache();
+            self.load_page_into(&mut cache, page_num)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            match &cache.pages[page_num] {
+                Some(page) => write_page(writer, page)?,
+                None => write_page(writer, &empty)?,
+            }
+        }
+        writer.flush()
+    }
+
+    /// Writes the live rows packed back to back after their count, with no
+    /// header or page padding. Unlike `write_to`, the output does not depend
+    /// on the page size, so `read_all_with_config` can load it into a table
+    /// laid out differently.
+    pub fn write_all<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let count = self.try_live_rows().map_err(crate::into_io_error)?;
+        writer.write_all(&(count as u64).to_le_bytes())?;
+        for row in crate::live_rows_io(self) {
+            writer.write_all(&row?.to_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Rebuilds an in-memory table from the output of `write_all`.
+    pub fn read_all<R: Read>(reader: &mut R) -> io::Result<Table> {
+        Table::read_all_with_config(reader, TableConfig::default())
+    }
+
+    pub fn read_all_with_config<R: Read>(reader: &mut R, config: TableConfig) -> io::Result<Table> {
+        let mut count = [0; 8];
+        reader.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count);
+        let mut table = Table::with_config(config);
+        if count > table.max_rows() as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("dump holds {} rows, more than the maximum of {}", count, table.max_rows()),
+            ));
+        }
+        let mut bytes = [0; ROW_SIZE];
+        for index in 0..count as usize {
+            reader.read_exact(&mut bytes)?;
+            table.row_slot(index).copy_from_slice(&bytes);
+            table.next_id = table.next_id.max(Row::from_bytes(&bytes).id.saturating_add(1));
+            table.num_rows = index + 1;
+        }
+        Ok(table)
+    }
+
+    /// Writes the whole database to `path`. The image is built in memory
+    /// before `path` is truncated, since `path` may be this table's own file
+    /// and still hold pages that have not been loaded yet.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut image = Vec::new();
+        self.write_to(&mut image)?;
+        let mut file = File::create(path).map_err(|err| with_path(err, path))?;
+        file.write_all(&image)?;
+        file.sync_all()
+    }
+
+    /// Writes a consistent copy of the database to `path`. A file-backed
+    /// table is flushed and its file copied; an in-memory or read-only one
+    /// is written out as by `save`. The copy goes to a temporary file next
+    /// to `path` that is synced and then renamed over it, so `path` never
+    /// holds a partial backup.
+    pub fn backup<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp_path = OsString::from(path);
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        let mut temp = File::create(&temp_path).map_err(|err| with_path(err, &temp_path))?;
+        let written = match &self.file {
+            Some(_) if !self.read_only => self.flush_dirty().and_then(|_| {
+                let mut source = self.file.as_ref().unwrap();
+                source.seek(SeekFrom::Start(0))?;
+                io::copy(&mut source, &mut temp).map(|_| ())
+            }),
+            _ => self.write_to(&mut temp),
+        };
+        if let Err(err) = written.and_then(|_| temp.sync_all()) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(err);
+        }
+        fs::rename(&temp_path, path).map_err(|err| with_path(err, path))
+    }
+
+    /// Writes the header and every dirty page to `writer`, which holds the
+    /// table's file image, then marks those pages clean.
+    pub fn flush_dirty_to<W: Write + Seek>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&FileHeader::for_table(self).to_bytes())?;
+        let page_size = self.config.page_size;
+        let cache = self.cache_mut();
+        for page_num in 0..cache.pages.len() {
+            if !cache.dirty[page_num] {
+                continue;
+            }
+            if let Some(page) = &cache.pages[page_num] {
+                writer.seek(SeekFrom::Start(page_offset_for(page_num, page_size)))?;
+                write_page(writer, page)?;
+            }
+            cache.dirty[page_num] = false;
+        }
+        writer.flush()
+    }
+
+    /// Discards every row, any open transaction and the id counter, and
+    /// empties the backing file and write-ahead log if the table has them.
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.transaction = None;
+        self.clear();
+        self.next_id = 1;
+        if let Some(file) = &self.file {
+            file.set_len(0)?;
+            file.sync_all()?;
+        }
+        self.wal_truncate()
+    }
+
+    /// Writes pages changed since the last flush back to the file the table
+    /// was opened from, then empties the write-ahead log. In-memory and
+    /// read-only tables have nothing to flush, and neither does a table in a
+    /// transaction: its file keeps the committed rows until `commit`.
+    pub fn flush_dirty(&mut self) -> io::Result<()> {
+        if self.read_only || self.in_transaction() {
+            return Ok(());
+        }
+        let Some(mut file) = self.file.take() else {
+            return Ok(());
+        };
+        let flushed: Vec<usize> = {
+            let cache = self.cache_mut();
+            (0..cache.pages.len()).filter(|&n| cache.dirty[n] && cache.pages[n].is_some()).collect()
+        };
+        let result = self
+            .flush_dirty_to(&mut file)
+            .and_then(|_| file.sync_all());
+        self.file = Some(file);
+        result?;
+        let cache = self.cache_mut();
+        for page_num in flushed {
+            cache.on_disk[page_num] = true;
+        }
+        self.wal_truncate()
+    }
+}
@@ -0,0 +1,33 @@
+// src/row_serde.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DbError, Row};
+
+/// How `Row` appears to serde: the strings without their NUL padding, and
+/// NULL columns as `None`, rather than the raw fixed-size byte arrays.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Row")]
+pub(crate) struct RowRepr {
+    id: u64,
+    username: Option<String>,
+    email: Option<String>,
+}
+
+impl From<Row> for RowRepr {
+    fn from(row: Row) -> Self {
+        RowRepr {
+            id: row.id,
+            username: row.username_str().map(|username| username.into_owned()),
+            email: row.email_str().map(|email| email.into_owned()),
+        }
+    }
+}
+
+impl TryFrom<RowRepr> for Row {
+    type Error = DbError;
+
+    fn try_from(repr: RowRepr) -> Result<Self, Self::Error> {
+        Row::try_new_nullable(repr.id, repr.username, repr.email)
+    }
+}
@@ -0,0 +1,46 @@
+// src/shared.rs
+
+use std::{
+    io::Write,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{execute_insert, execute_select, ExecuteResult, Row, Select, SelectSettings, Table};
+
+/// A table handle that can be cloned across threads. Reads take a shared
+/// lock, so any number of readers run at once; writes take the exclusive
+/// lock that `Table::row_slot`'s `&mut self` requires.
+#[derive(Clone, Debug, Default)]
+pub struct SharedTable {
+    inner: Arc<RwLock<Table>>,
+}
+
+impl SharedTable {
+    pub fn new(table: Table) -> Self {
+        SharedTable {
+            inner: Arc::new(RwLock::new(table)),
+        }
+    }
+
+    pub fn insert(&self, row: &Row) -> ExecuteResult {
+        execute_insert(&mut self.write(), row)
+    }
+
+    pub fn select_all<W: Write>(&self, writer: &mut W) -> ExecuteResult {
+        execute_select(&self.read(), &Select::default(), &SelectSettings::default(), writer)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.read().num_rows
+    }
+
+    /// Locks the table for reading, for anything not covered above.
+    pub fn read(&self) -> RwLockReadGuard<'_, Table> {
+        self.inner.read().expect("table lock poisoned")
+    }
+
+    /// Locks the table for writing, for anything not covered above.
+    pub fn write(&self) -> RwLockWriteGuard<'_, Table> {
+        self.inner.write().expect("table lock poisoned")
+    }
+}
@@ -0,0 +1,108 @@
+// src/tokenizer.rs
+
+#[derive(Debug, PartialEq)]
+pub enum TokenizeError {
+    UnterminatedQuote,
+}
+
+/// A word of input. `start` and `end` are character offsets into the input,
+/// `end` exclusive, and span any quotes around the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    /// Whether any part was quoted, so `"null"` is not the NULL keyword.
+    pub quoted: bool,
+}
+
+/// Splits `input` on runs of whitespace, keeping double-quoted runs
+/// together so that fields may contain spaces. Inside quotes `\"` and `\\`
+/// are unescaped.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, TokenizeError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    let mut start = 0;
+    let mut chars = input.chars().enumerate();
+
+    while let Some((position, c)) = chars.next() {
+        if !in_token && !c.is_whitespace() {
+            start = position;
+        }
+        match c {
+            '"' => {
+                in_token = true;
+                quoted = true;
+                read_quoted(&mut chars, &mut current)?;
+            }
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(Token { text: std::mem::take(&mut current), start, end: position, quoted });
+                    in_token = false;
+                    quoted = false;
+                }
+            }
+            c => {
+                in_token = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_token {
+        tokens.push(Token { text: current, start, end: input.chars().count(), quoted });
+    }
+    Ok(tokens)
+}
+
+/// Reads the rest of a quoted run whose opening quote has been consumed,
+/// pushing its unescaped text onto `text`.
+fn read_quoted<I: Iterator<Item = (usize, char)>>(chars: &mut I, text: &mut String) -> Result<(), TokenizeError> {
+    loop {
+        match chars.next().map(|(_, c)| c) {
+            Some('"') => return Ok(()),
+            Some('\\') => match chars.next().map(|(_, c)| c) {
+                Some(escaped @ ('"' | '\\')) => text.push(escaped),
+                Some(other) => {
+                    text.push('\\');
+                    text.push(other);
+                }
+                None => return Err(TokenizeError::UnterminatedQuote),
+            },
+            Some(other) => text.push(other),
+            None => return Err(TokenizeError::UnterminatedQuote),
+        }
+    }
+}
+
+/// The byte offset of the first `target` outside double quotes, with quotes
+/// read the way `tokenize` reads them.
+pub(crate) fn find_unquoted(input: &str, target: char) -> Result<Option<usize>, TokenizeError> {
+    let mut chars = input.char_indices();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '"' => read_quoted(&mut chars, &mut String::new())?,
+            c if c == target => return Ok(Some(position)),
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Splits `input` at each `delimiter` outside double quotes.
+pub(crate) fn split_unquoted(input: &str, delimiter: char) -> Result<Vec<&str>, TokenizeError> {
+    let mut fields = Vec::new();
+    let mut rest = input;
+    while let Some(end) = find_unquoted(rest, delimiter)? {
+        fields.push(&rest[..end]);
+        rest = &rest[end + delimiter.len_utf8()..];
+    }
+    fields.push(rest);
+    Ok(fields)
+}
+
+/// The text of each token, for parsers that don't need positions.
+pub fn words(input: &str) -> Result<Vec<String>, TokenizeError> {
+    Ok(tokenize(input)?.into_iter().map(|token| token.text).collect())
+}
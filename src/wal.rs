@@ -0,0 +1,152 @@
+// src/wal.rs
+
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{crc32, pager::with_path, serialize, Row, Table, ROW_SIZE};
+
+// target index + serialized row + checksum over both
+pub const WAL_RECORD_SIZE: usize = 8 + ROW_SIZE + 4;
+
+/// Transaction boundaries are logged as records whose index no row can
+/// have, so replay can tell committed writes from abandoned ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum WalMarker {
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl WalMarker {
+    const fn index(self) -> u64 {
+        match self {
+            WalMarker::Begin => u64::MAX,
+            WalMarker::Commit => u64::MAX - 1,
+            WalMarker::Rollback => u64::MAX - 2,
+        }
+    }
+
+    fn from_index(index: u64) -> Option<WalMarker> {
+        [WalMarker::Begin, WalMarker::Commit, WalMarker::Rollback]
+            .into_iter()
+            .find(|marker| marker.index() == index)
+    }
+}
+
+pub fn wal_path<P: AsRef<Path>>(db_path: P) -> PathBuf {
+    let mut path = OsString::from(db_path.as_ref());
+    path.push(".wal");
+    path.into()
+}
+
+pub(crate) fn open_wal(db_path: &Path) -> io::Result<File> {
+    let path = wal_path(db_path);
+    OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&path)
+        .map_err(|err| with_path(err, &path))
+}
+
+fn encode_record(index: u64, row_bytes: &[u8; ROW_SIZE]) -> [u8; WAL_RECORD_SIZE] {
+    let mut record = [0; WAL_RECORD_SIZE];
+    record[..8].copy_from_slice(&index.to_le_bytes());
+    record[8..8 + ROW_SIZE].copy_from_slice(row_bytes);
+    let checksum = crc32(&record[..8 + ROW_SIZE]);
+    record[8 + ROW_SIZE..].copy_from_slice(&checksum.to_le_bytes());
+    record
+}
+
+/// Decodes a record, returning `None` for a torn or corrupt one.
+fn decode_record(record: &[u8; WAL_RECORD_SIZE]) -> Option<(u64, Row)> {
+    let stored = u32::from_le_bytes(record[8 + ROW_SIZE..].try_into().unwrap());
+    if stored != crc32(&record[..8 + ROW_SIZE]) {
+        return None;
+    }
+    let index = u64::from_le_bytes(record[..8].try_into().unwrap());
+    Some((index, Row::from_bytes(record[8..8 + ROW_SIZE].try_into().unwrap())))
+}
+
+impl Table {
+    /// Appends a row write to the write-ahead log, if the table has one,
+    /// and syncs it so the write survives a crash before the next flush.
+    pub(crate) fn wal_append(&mut self, index: usize, row: &Row) -> io::Result<()> {
+        let Some(wal) = &mut self.wal else {
+            return Ok(());
+        };
+        wal.write_all(&encode_record(index as u64, &row.to_bytes()))?;
+        wal.sync_data()
+    }
+
+    /// Logs a transaction boundary, synced like a row write.
+    pub(crate) fn wal_mark(&mut self, marker: WalMarker) -> io::Result<()> {
+        let Some(wal) = &mut self.wal else {
+            return Ok(());
+        };
+        wal.write_all(&encode_record(marker.index(), &[0; ROW_SIZE]))?;
+        wal.sync_data()
+    }
+
+    /// Empties the write-ahead log once its records are in the main file.
+    pub(crate) fn wal_truncate(&mut self) -> io::Result<()> {
+        let Some(wal) = &mut self.wal else {
+            return Ok(());
+        };
+        wal.set_len(0)?;
+        wal.seek(SeekFrom::Start(0))?;
+        wal.sync_all()
+    }
+
+    /// Re-applies logged row writes on top of the table's current pages,
+    /// stopping at the end of the log or the first incomplete record.
+    /// Writes made inside a transaction are held back until its commit
+    /// marker; those rolled back or never committed are dropped. Returns
+    /// the number of records applied.
+    pub fn replay_wal<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let mut record = [0; WAL_RECORD_SIZE];
+        let mut applied = 0;
+        let mut pending: Option<Vec<(usize, Row)>> = None;
+        loop {
+            match reader.read_exact(&mut record) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+            let Some((index, row)) = decode_record(&record) else {
+                break;
+            };
+            match WalMarker::from_index(index) {
+                Some(WalMarker::Begin) => pending = Some(Vec::new()),
+                Some(WalMarker::Commit) => {
+                    for (index, row) in pending.take().unwrap_or_default() {
+                        self.apply_logged_row(index, &row)?;
+                        applied += 1;
+                    }
+                }
+                Some(WalMarker::Rollback) => pending = None,
+                None if index >= self.max_rows() as u64 => break,
+                None => match &mut pending {
+                    Some(writes) => writes.push((index as usize, row)),
+                    None => {
+                        self.apply_logged_row(index as usize, &row)?;
+                        applied += 1;
+                    }
+                },
+            }
+        }
+        Ok(applied)
+    }
+
+    fn apply_logged_row(&mut self, index: usize, row: &Row) -> io::Result<()> {
+        let slot = self.try_row_slot(index).map_err(crate::into_io_error)?;
+        serialize(row, slot);
+        self.num_rows = self.num_rows.max(index + 1);
+        self.next_id = self.next_id.max(row.id.saturating_add(1));
+        Ok(())
+    }
+}
@@ -1,12 +1,20 @@
 // tests/db_tests.rs
 
-use rsql::{Row, Table, serialize, deserialize, ROW_SIZE, run_repl};
-use std::io::{BufReader, Cursor};
+use rsql::{Row, Table, serialize, deserialize, ROW_SIZE, run_repl, execute_insert, export_csv, import_csv, import_jsonl, dump, ImportError, execute_select_json, OpenError, ReadError, page_offset, crc32, ROWS_PER_PAGE, PAGE_SIZE, DbError, execute_select, Select, SelectSettings, SELECT_FLUSH_INTERVAL, open_table_from_args, run_line, LineOutcome, ReplState, parse, ParseError, StatementType, Filter, Column, OrderBy, Aggregate, RowEnd, wal_path, WAL_RECORD_SIZE, SharedTable, TableActor, ExecuteResult, TableConfig, TruncatedPage, IntegrityError, ColumnLimits, COLUMN_USERNAME_SIZE, COLUMN_EMAIL_SIZE};
+use std::io::{self, BufReader, Cursor, Seek, SeekFrom, Write};
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn temp_db_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rsql_{}_{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(wal_path(&path));
+        path
+    }
+
     // DB tests
     #[test]
     fn test_row_creation() {
@@ -86,7 +94,7 @@ mod tests {
         // Build expected output
         let mut expected = String::new();
         for _i in 0..x {
-            expected.push_str("rsql > "); // Prompt for each insert
+            expected.push_str("rsql > 1 row inserted\n"); // Prompt and result for each insert
         }
         expected.push_str("rsql > "); // Prompt for select
         for i in 0..x {
@@ -126,7 +134,16 @@ mod tests {
         for i in 0..x {
             expected.push_str("rsql > "); // Prompt for each insert
             if i >= rsql::MAX_ROWS {
-                expected.push_str(&format!("Row not inserted, table full '{} user{} user{}@example.com'\n", i, i, i));
+                expected.push_str(&format!(
+                    "Row not inserted, table full ({}/{} rows) '{} user{} user{}@example.com'\n",
+                    rsql::MAX_ROWS,
+                    rsql::MAX_ROWS,
+                    i,
+                    i,
+                    i
+                ));
+            } else {
+                expected.push_str("1 row inserted\n");
             }
         }
         expected.push_str("rsql > "); // Prompt before .exit
@@ -163,6 +180,2571 @@ mod tests {
         run_repl(&mut table, &mut reader, &mut output);
 
         let output_str = String::from_utf8(output).unwrap();
-        assert!(output_str.contains("Syntax Error in 'insert 1'"));
+        assert!(output_str.contains("Syntax error at column 9: got id=1, missing username and email"));
+    }
+
+    #[test]
+    fn test_repl_schema() {
+        let mut table = Table::new();
+
+        let input = ".schema\n.exit\n";
+        let mut reader = BufReader::new(Cursor::new(input));
+        let mut output = Vec::new();
+
+        run_repl(&mut table, &mut reader, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        let expected = "rsql > \
+id: u64 (offset 0, size 8)
+username: char[32] (offset 8, size 32)
+email: char[255] (offset 40, size 255)
+nulls: u8 (offset 295, size 1)
+row size: 296
+rsql > ";
+        assert_eq!(output_str, expected);
+    }
+
+    #[test]
+    fn test_repl_quoted_username() {
+        let mut table = Table::new();
+
+        let input = "insert 1 \"John Doe\" john@example.com\nselect\n.exit\n";
+        let mut reader = BufReader::new(Cursor::new(input));
+        let mut output = Vec::new();
+
+        run_repl(&mut table, &mut reader, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        assert_eq!(output_str, "rsql > 1 row inserted\nrsql > 1 John Doe john@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_unterminated_quote() {
+        let mut table = Table::new();
+
+        let input = "insert 1 \"John Doe john@example.com\n.exit\n";
+        let mut reader = BufReader::new(Cursor::new(input));
+        let mut output = Vec::new();
+
+        run_repl(&mut table, &mut reader, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        assert!(output_str.contains("Syntax Error in 'insert 1 \"John Doe john@example.com'"));
+        assert_eq!(table.num_rows, 0);
+    }
+
+    #[test]
+    fn test_repl_case_insensitive_keywords() {
+        let mut table = Table::new();
+
+        let input = "INSERT 1 Alice Alice@Example.com\nSELECT\nSelect\n.EXIT\n";
+        let mut reader = BufReader::new(Cursor::new(input));
+        let mut output = Vec::new();
+
+        run_repl(&mut table, &mut reader, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        let expected = "rsql > 1 row inserted
+rsql > 1 Alice Alice@Example.com
+rsql > 1 Alice Alice@Example.com
+rsql > ";
+        assert_eq!(output_str, expected);
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let mut table = Table::new();
+        execute_insert(&mut table, &Row::new(1, "doe, john".to_string(), "john@example.com".to_string()));
+        execute_insert(&mut table, &Row::new(2, "jane".to_string(), "jane@example.com".to_string()));
+
+        let mut output = Vec::new();
+        export_csv(&table, &mut output).unwrap();
+
+        let output_str = String::from_utf8(output).unwrap();
+        assert_eq!(output_str, "1,\"doe, john\",john@example.com\n2,jane,jane@example.com\n");
+    }
+
+    #[test]
+    fn test_import_csv() {
+        let mut table = Table::new();
+        let long_email = format!("{}@example.com", "x".repeat(300));
+        let csv = format!(
+            "1,alice,alice@example.com\n2,\"doe, john\",john@example.com\n3,bob,bob@example.com\n4,eve,{}\n",
+            long_email
+        );
+
+        let result = import_csv(&mut table, &mut Cursor::new(csv));
+
+        match result {
+            Err(err @ ImportError::StringTooLong { line: 4 }) => {
+                assert_eq!(err.to_string(), "line 4: string is too long");
+            }
+            other => panic!("expected StringTooLong on line 4, got {:?}", other),
+        }
+        assert_eq!(table.num_rows, 3);
+        assert_eq!(deserialize(table.row_slot(1)), Row::new(2, "doe, john".to_string(), "john@example.com".to_string()));
+    }
+
+    #[test]
+    fn test_repl_duplicate_key_and_long_string() {
+        let mut table = Table::new();
+
+        let input = format!("insert 1 a a@b.com\ninsert 1 b b@b.com\ninsert 2 {} c@b.com\n.exit\n", "u".repeat(33));
+        let mut reader = BufReader::new(Cursor::new(input));
+        let mut output = Vec::new();
+
+        run_repl(&mut table, &mut reader, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        assert_eq!(output_str, "rsql > 1 row inserted\nrsql > Error: Duplicate key.\nrsql > String is too long.\nrsql > ");
+        assert_eq!(table.num_rows, 1);
+    }
+
+    #[test]
+    fn test_execute_select_json() {
+        let mut table = Table::new();
+        execute_insert(&mut table, &Row::new(1, "alice".to_string(), "alice@example.com".to_string()));
+        execute_insert(&mut table, &Row::new(2, "say \"hi\"\\".to_string(), "bob@example.com".to_string()));
+
+        let mut output = Vec::new();
+        execute_select_json(&table, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output_str,
+            r#"[{"id":1,"username":"alice","email":"alice@example.com"},{"id":2,"username":"say \"hi\"\\","email":"bob@example.com"}]"#.to_string() + "\n"
+        );
+    }
+
+    #[test]
+    fn test_open_saved_file() {
+        let path = temp_db_path("open_saved");
+        let mut table = Table::new();
+        for i in 0..20 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        table.save(&path).unwrap();
+
+        let mut reopened = Table::open(&path).unwrap();
+
+        assert_eq!(reopened.num_rows, 20);
+        for i in 0..20 {
+            assert_eq!(deserialize(table.row_slot(i)), deserialize(reopened.row_slot(i)));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_bad_magic() {
+        let path = temp_db_path("bad_magic");
+        Table::new().save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[0] = b'X';
+        std::fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(Table::open(&path), Err(OpenError::BadMagic)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Records the file offset of every write instead of storing the bytes.
+    #[derive(Default)]
+    struct OffsetRecorder {
+        position: u64,
+        writes: Vec<u64>,
+    }
+
+    impl Write for OffsetRecorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes.push(self.position);
+            self.position += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl OffsetRecorder {
+        fn pages_written(&self) -> Vec<usize> {
+            let mut pages: Vec<usize> = self
+                .writes
+                .iter()
+                .filter(|&&offset| offset >= page_offset(0))
+                .map(|&offset| ((offset - page_offset(0)) / rsql::PAGE_SIZE as u64) as usize)
+                .collect();
+            pages.dedup();
+            pages
+        }
+    }
+
+    impl Seek for OffsetRecorder {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            match pos {
+                SeekFrom::Start(offset) => self.position = offset,
+                _ => unimplemented!(),
+            }
+            Ok(self.position)
+        }
+    }
+
+    #[test]
+    fn test_flush_dirty_writes_only_changed_pages() {
+        let mut table = Table::new();
+        for i in 0..(ROWS_PER_PAGE + 1) as u64 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        let mut recorder = OffsetRecorder::default();
+        table.flush_dirty_to(&mut recorder).unwrap();
+        assert_eq!(recorder.writes[0], 0);
+        assert_eq!(recorder.pages_written(), vec![0, 1]);
+
+        let row = Row::new(0, "changed".to_string(), "changed@example.com".to_string());
+        serialize(&row, table.row_slot(0));
+        let mut recorder = OffsetRecorder::default();
+        table.flush_dirty_to(&mut recorder).unwrap();
+
+        assert_eq!(recorder.pages_written(), vec![0]);
+    }
+
+    #[test]
+    fn test_flush_dirty_round_trip() {
+        let path = temp_db_path("flush_dirty");
+        let mut table = Table::open(&path).unwrap();
+        execute_insert(&mut table, &Row::new(1, "alice".to_string(), "alice@example.com".to_string()));
+        table.flush_dirty().unwrap();
+        drop(table);
+
+        let mut reopened = Table::open(&path).unwrap();
+        assert_eq!(reopened.num_rows, 1);
+        assert_eq!(deserialize(reopened.row_slot(0)).id, 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_load_detects_checksum_mismatch() {
+        let path = corrupt_second_page("checksum");
+
+        // pages are only verified as they are loaded
+        let mut table = Table::open(&path).unwrap();
+        assert_eq!(table.page_loads(), 0);
+        assert!(table.try_read_slot(0).is_ok());
+        let result = table.try_read_slot(ROWS_PER_PAGE);
+        assert!(matches!(result, Err(DbError::Read(ReadError::ChecksumMismatch { page: 1 }))));
+
+        // a statement that reaches the bad page reports it instead of panicking
+        let output = run_script(&mut table, &format!("select where id = {}\nselect\n.exit\n", ROWS_PER_PAGE));
+        assert_eq!(output.matches("Error: checksum mismatch on page 1\n").count(), 2);
+        drop(table);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_db_error_display() {
+        assert_eq!(DbError::DuplicateKey.to_string(), "Error: Duplicate key.");
+        assert_eq!(DbError::StringTooLong.to_string(), "String is too long.");
+        assert_eq!(DbError::Parse("insert 1".to_string()).to_string(), "Syntax Error in 'insert 1'");
+        let io_error = DbError::from(io::Error::new(io::ErrorKind::NotFound, "missing.db"));
+        assert_eq!(io_error.to_string(), "Error: missing.db");
+    }
+
+    #[test]
+    fn test_row_try_new_rejects_long_strings() {
+        assert!(matches!(
+            Row::try_new(1, "u".repeat(33), "a@b.com".to_string()),
+            Err(DbError::StringTooLong)
+        ));
+        assert!(Row::try_new(1, "u".repeat(32), "a@b.com".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_repl_invalid_email() {
+        let mut table = Table::new();
+
+        let input = "insert 1 user noemail\ninsert 1 user a@b.com\nselect\n.exit\n";
+        let mut reader = BufReader::new(Cursor::new(input));
+        let mut output = Vec::new();
+
+        run_repl(&mut table, &mut reader, &mut output);
+
+        let output_str = String::from_utf8(output).unwrap();
+        assert_eq!(output_str, "rsql > Invalid email: noemail\nrsql > 1 row inserted\nrsql > 1 user a@b.com\nrsql > ");
+    }
+
+    fn run_script(table: &mut Table, input: &str) -> String {
+        let mut reader = BufReader::new(Cursor::new(input.to_string()));
+        let mut output = Vec::new();
+        run_repl(table, &mut reader, &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_repl_select_columns() {
+        let mut table = Table::new();
+        let setup = "insert 1 alice alice@example.com\ninsert 2 bob bob@example.com\n";
+
+        let output = run_script(&mut table, &format!("{}select id\n.exit\n", setup));
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > 1 row inserted\nrsql > 1\n2\nrsql > ");
+
+        let output = run_script(&mut table, "select email, id\nselect username,email\n.exit\n");
+        let expected = "rsql > \
+alice@example.com 1
+bob@example.com 2
+rsql > alice alice@example.com
+bob bob@example.com
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_select_unknown_column() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "select id, phone\n.exit\n");
+
+        assert_eq!(output, "rsql > Syntax Error in 'select id, phone': unknown column 'phone'\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_select_order_by_id() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 3 c c@example.com\ninsert 1 a a@example.com\ninsert 2 b b@example.com\n.exit\n");
+
+        let output = run_script(&mut table, "select order by id\nselect id order by id desc\nselect\n.exit\n");
+
+        let expected = "rsql > \
+1 a a@example.com
+2 b b@example.com
+3 c c@example.com
+rsql > 3
+2
+1
+rsql > 3 c c@example.com
+1 a a@example.com
+2 b b@example.com
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_select_count() {
+        let mut table = Table::new();
+        let mut input = String::new();
+        for i in 0..5 {
+            input.push_str(&format!("insert {} user{} user{}@example.com\n", i, i, i));
+        }
+        run_script(&mut table, &format!("{}.exit\n", input));
+
+        let output = run_script(&mut table, "select count\n.exit\n");
+
+        assert_eq!(output, "rsql > 5\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_select_min_max_avg() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 4 a a@example.com\ninsert 1 b b@example.com\ninsert 2 c c@example.com\n.exit\n");
+
+        let output = run_script(&mut table, "select min\nselect max\nselect avg\n.exit\n");
+
+        assert_eq!(output, "rsql > 1\nrsql > 4\nrsql > 2.33\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_select_aggregates_on_empty_table() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "select min\nselect max\nselect avg\n.exit\n");
+
+        assert_eq!(output, "rsql > No rows.\nrsql > No rows.\nrsql > No rows.\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_select_where_email_like() {
+        let mut table = Table::new();
+        run_script(
+            &mut table,
+            "insert 1 alice alice@example.com\ninsert 2 bob bob@test.org\ninsert 3 albert albert@example.com\n.exit\n",
+        );
+
+        let output = run_script(
+            &mut table,
+            "select where email like %@example.com\nselect where email like bob%\nselect where email like %@nowhere.net\n.exit\n",
+        );
+
+        let expected = "rsql > \
+1 alice alice@example.com
+3 albert albert@example.com
+rsql > 2 bob bob@test.org
+rsql > rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_stats() {
+        let mut table = Table::new();
+        let mut input = String::new();
+        for i in 0..(ROWS_PER_PAGE + 1) {
+            input.push_str(&format!("insert {} user{} user{}@example.com\n", i, i, i));
+        }
+        run_script(&mut table, &format!("{}.exit\n", input));
+
+        let output = run_script(&mut table, ".stats\n.exit\n");
+
+        let expected = format!(
+            "rsql > rows: {}\npages allocated: 2/100\nmemory used: {} bytes\ncapacity used: {:.2}%\nrsql > ",
+            ROWS_PER_PAGE + 1,
+            2 * rsql::PAGE_SIZE,
+            (ROWS_PER_PAGE + 1) as f64 * 100.0 / rsql::MAX_ROWS as f64
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_delete_all() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 1 a a@example.com\ninsert 2 b b@example.com\n.exit\n");
+
+        let output = run_script(&mut table, "delete\nselect\n.exit\n");
+
+        assert_eq!(output, "rsql > 2 rows deleted\nrsql > rsql > ");
+        assert_eq!(table.num_rows, 0);
+        assert_eq!(table.allocated_pages(), 0);
+
+        let output = run_script(&mut table, "insert 1 c c@example.com\nselect\n.exit\n");
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > 1 c c@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_timer() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert 1 a a@example.com\n.timer on\nselect\n.timer off\nselect\n.exit\n");
+
+        let lines: Vec<&str> = output.split('\n').collect();
+        assert_eq!(lines[0], "rsql > 1 row inserted");
+        assert_eq!(lines[1], "rsql > rsql > 1 a a@example.com");
+        let timing = lines[2].strip_prefix("Run Time: real ").expect("missing timing line");
+        assert!(timing.parse::<f64>().is_ok(), "unexpected timing '{}'", timing);
+        assert_eq!(lines[3], "rsql > rsql > 1 a a@example.com");
+        assert_eq!(lines[4], "rsql > ");
+    }
+
+    #[derive(Default)]
+    struct FlushCounter {
+        bytes: usize,
+        flushes: usize,
+    }
+
+    impl Write for FlushCounter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.bytes += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_select_flushes_periodically() {
+        let mut table = Table::new();
+        let rows = SELECT_FLUSH_INTERVAL * 3 + 1;
+        for i in 0..rows as u64 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        let mut writer = FlushCounter::default();
+
+        execute_select(&table, &Select::default(), &SelectSettings::default(), &mut writer);
+
+        assert!(writer.bytes > 0);
+        assert!(writer.flushes > 1);
+        assert_eq!(writer.flushes, rows / SELECT_FLUSH_INTERVAL + 1);
+    }
+
+    #[test]
+    fn test_repl_reports_affected_rows() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert 1 a a@example.com\ninsert 2 b b@example.com\ninsert 3 c c@example.com\ndelete\ndelete\n.exit\n",
+        );
+
+        let expected = "rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > 3 rows deleted
+rsql > 0 rows deleted
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_insert_or_replace() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert or replace 1 alice alice@example.com\nselect\n.exit\n");
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > 1 alice alice@example.com\nrsql > ");
+        assert_eq!(table.num_rows, 1);
+
+        let output = run_script(
+            &mut table,
+            "insert 2 bob bob@example.com\nINSERT OR REPLACE 1 alicia alicia@example.com\nselect\n.exit\n",
+        );
+        let expected = "rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > 1 alicia alicia@example.com
+2 bob bob@example.com
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(table.num_rows, 2);
+    }
+
+    #[test]
+    fn test_open_table_from_args() {
+        let table = open_table_from_args(vec!["rsql".to_string()]).unwrap();
+        assert_eq!(table.num_rows, 0);
+
+        let path = temp_db_path("from_args");
+        let mut table = Table::new();
+        execute_insert(&mut table, &Row::new(7, "g".to_string(), "g@example.com".to_string()));
+        table.save(&path).unwrap();
+        let table = open_table_from_args(vec!["rsql".to_string(), path.clone()]).unwrap();
+        assert_eq!(table.num_rows, 1);
+        std::fs::remove_file(&path).unwrap();
+
+        let directory = std::env::temp_dir().to_str().unwrap().to_string();
+        assert!(matches!(open_table_from_args(vec!["rsql".to_string(), directory]), Err(OpenError::Io(_))));
+    }
+
+    #[test]
+    fn test_repl_save_and_open() {
+        let path = temp_db_path("save_open");
+        let mut table = Table::new();
+        run_script(
+            &mut table,
+            &format!("insert 1 alice alice@example.com\ninsert 2 bob bob@example.com\n.save {}\n.exit\n", path),
+        );
+
+        let mut other = Table::new();
+        let output = run_script(&mut other, &format!(".open {}\nselect\n.exit\n", path));
+
+        assert_eq!(output, "rsql > rsql > 1 alice alice@example.com\n2 bob bob@example.com\nrsql > ");
+        assert_eq!(other.num_rows, table.num_rows);
+        for i in 0..table.num_rows {
+            assert_eq!(deserialize(other.row_slot(i)), deserialize(table.row_slot(i)));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_repl_save_to_unwritable_path() {
+        let mut table = Table::new();
+        let path = std::env::temp_dir().join("rsql_missing_dir").join("db.rsql");
+
+        let output = run_script(&mut table, &format!(".save {}\n.exit\n", path.display()));
+
+        assert!(output.contains(&format!("Error saving to '{}'", path.display())));
+    }
+
+    #[test]
+    fn test_repl_exits_on_eof() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert 1 a a@example.com\nselect\n");
+
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > 1 a a@example.com\nrsql > \n");
+    }
+
+    #[test]
+    fn test_repl_ignores_blank_lines() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert 1 a a@example.com\n\n   \ninsert 2 b b@example.com\n.exit\n");
+
+        assert!(!output.contains("Unrecognized keyword"));
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > rsql > rsql > 1 row inserted\nrsql > ");
+        assert_eq!(table.num_rows, 2);
+    }
+
+    #[test]
+    fn test_repl_transaction_rollback() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 1 a a@example.com\n.exit\n");
+
+        let output = run_script(
+            &mut table,
+            "begin\ninsert 2 b b@example.com\ninsert 3 c c@example.com\nrollback\nselect\n.exit\n",
+        );
+
+        assert_eq!(table.num_rows, 1);
+        assert!(output.ends_with("rsql > 1 a a@example.com\nrsql > "));
+    }
+
+    #[test]
+    fn test_repl_transaction_rollback_restores_changed_rows() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 1 a a@example.com\ninsert 2 b b@example.com\n.exit\n");
+
+        run_script(&mut table, "begin\ninsert or replace 1 z z@example.com\ndelete\nrollback\n.exit\n");
+
+        let output = run_script(&mut table, "select\n.exit\n");
+        assert_eq!(output, "rsql > 1 a a@example.com\n2 b b@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_transaction_commit() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "commit\nbegin\nbegin\ninsert 1 a a@example.com\ncommit\nrollback\n.exit\n",
+        );
+
+        let expected = "rsql > Error: no transaction is active.
+rsql > rsql > Error: a transaction is already active.
+rsql > 1 row inserted
+rsql > rsql > Error: no transaction is active.
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(table.num_rows, 1);
+    }
+
+    #[test]
+    fn test_wal_replays_unflushed_inserts_on_open() {
+        let path = temp_db_path("wal_replay");
+        {
+            let mut table = Table::open(&path).unwrap();
+            run_script(&mut table, "insert 1 a a@example.com\ninsert 2 b b@example.com\n.exit\n");
+            // dropped without flush_dirty, as if the process crashed
+        }
+        assert_eq!(std::fs::metadata(wal_path(&path)).unwrap().len(), 2 * WAL_RECORD_SIZE as u64);
+
+        let mut reopened = Table::open(&path).unwrap();
+        let output = run_script(&mut reopened, "select\n.exit\n");
+        assert_eq!(output, "rsql > 1 a a@example.com\n2 b b@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_wal_replay_stops_at_torn_record() {
+        let path = temp_db_path("wal_torn");
+        {
+            let mut table = Table::open(&path).unwrap();
+            run_script(&mut table, "insert 1 a a@example.com\ninsert 2 b b@example.com\n.exit\n");
+        }
+        let wal = std::fs::OpenOptions::new().write(true).open(wal_path(&path)).unwrap();
+        wal.set_len((2 * WAL_RECORD_SIZE - 10) as u64).unwrap();
+
+        let mut table = Table::new();
+        let mut log = std::fs::File::open(wal_path(&path)).unwrap();
+        assert_eq!(table.replay_wal(&mut log).unwrap(), 1);
+        assert_eq!(table.num_rows, 1);
+    }
+
+    #[test]
+    fn test_flush_truncates_wal() {
+        let path = temp_db_path("wal_truncate");
+        let mut table = Table::open(&path).unwrap();
+        run_script(&mut table, "insert 1 a a@example.com\ninsert or replace 1 b b@example.com\n.exit\n");
+        assert_eq!(std::fs::metadata(wal_path(&path)).unwrap().len(), 2 * WAL_RECORD_SIZE as u64);
+
+        table.flush_dirty().unwrap();
+        assert_eq!(std::fs::metadata(wal_path(&path)).unwrap().len(), 0);
+        drop(table);
+
+        let mut reopened = Table::open(&path).unwrap();
+        let output = run_script(&mut reopened, "select\n.exit\n");
+        assert_eq!(output, "rsql > 1 b b@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_delete_checkpoints_file_backed_table() {
+        let path = temp_db_path("wal_delete");
+        {
+            let mut table = Table::open(&path).unwrap();
+            run_script(&mut table, "insert 1 a a@example.com\ndelete\ninsert 2 b b@example.com\n.exit\n");
+        }
+
+        let mut reopened = Table::open(&path).unwrap();
+        let output = run_script(&mut reopened, "select\n.exit\n");
+        assert_eq!(output, "rsql > 2 b b@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_shared_table_concurrent_readers_and_writer() {
+        let shared = SharedTable::new(Table::new());
+
+        let writer = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                for id in 1..=200 {
+                    let row = Row::new(id, format!("user{}", id), format!("user{}@example.com", id));
+                    assert!(matches!(shared.insert(&row), ExecuteResult::Inserted { .. }));
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        let mut output = Vec::new();
+                        assert!(matches!(shared.select_all(&mut output), ExecuteResult::Success { .. }));
+                        // every row printed was complete when the read lock was taken
+                        let text = String::from_utf8(output).unwrap();
+                        assert!(text.lines().all(|line| line.split(' ').count() == 3));
+                    }
+                })
+            })
+            .collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(shared.num_rows(), 200);
+    }
+
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// A minimal single-threaded runtime: polls every future in turn, parking
+    /// the thread until one of them is woken, and returns their outputs.
+    fn block_on_all<T>(mut futures: Vec<std::pin::Pin<Box<dyn std::future::Future<Output = T> + '_>>>) -> Vec<T> {
+        let waker = std::task::Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut outputs: Vec<Option<T>> = futures.iter().map(|_| None).collect();
+        while outputs.iter().any(Option::is_none) {
+            for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+                if output.is_none() {
+                    if let std::task::Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                        *output = Some(value);
+                    }
+                }
+            }
+            if outputs.iter().any(Option::is_none) {
+                std::thread::park();
+            }
+        }
+        outputs.into_iter().map(Option::unwrap).collect()
+    }
+
+    #[test]
+    fn test_table_actor_concurrent_inserts() {
+        let actor = TableActor::spawn(Table::new());
+
+        let inserts = (1..=25)
+            .map(|id| {
+                let actor = actor.clone();
+                let row = Row::new(id, format!("user{}", id), format!("user{}@example.com", id));
+                Box::pin(async move { actor.insert(row).await }) as std::pin::Pin<Box<dyn std::future::Future<Output = _>>>
+            })
+            .collect();
+        let results = block_on_all(inserts);
+        assert!(results.iter().all(|result| matches!(result, ExecuteResult::Inserted { .. })));
+
+        let count = block_on_all(vec![Box::pin(actor.count())]);
+        assert_eq!(count, vec![25]);
+        let rows = block_on_all(vec![Box::pin(actor.select())]).pop().unwrap();
+        let mut ids: Vec<u64> = rows.iter().map(|row| row.id).collect();
+        ids.sort();
+        assert_eq!(ids, (1..=25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_row_bytes_round_trip() {
+        let row = Row::new(42, "alice".to_string(), "alice@example.com".to_string());
+        let bytes = row.to_bytes();
+        assert_eq!(bytes.len(), ROW_SIZE);
+        assert_eq!(Row::from_bytes(&bytes), row);
+
+        let mut buffer = [0; ROW_SIZE];
+        serialize(&row, &mut buffer);
+        assert_eq!(bytes, buffer);
+    }
+
+    #[test]
+    fn test_row_str_accessors() {
+        let row = Row::new(1, "alice".to_string(), "alice@example.com".to_string());
+        assert_eq!(row.username_str().unwrap(), "alice");
+        assert_eq!(row.email_str().unwrap(), "alice@example.com");
+
+        let full = "u".repeat(32);
+        let row = Row::new(2, full.clone(), "b@example.com".to_string());
+        assert_eq!(row.username_str().unwrap(), full);
+
+        let mut row = Row::new(3, "x".to_string(), "c@example.com".to_string());
+        row.username[0] = 0xFF;
+        assert_eq!(row.username_str().unwrap(), "Invalid UTF-8");
+        assert_eq!(row.to_string(), "3 Invalid UTF-8 c@example.com");
+    }
+
+    #[test]
+    fn test_table_clone_is_independent_snapshot() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 1 a a@example.com\ninsert 2 b b@example.com\n.exit\n");
+
+        let mut snapshot = table.clone();
+        assert_eq!(snapshot.allocated_pages(), table.allocated_pages());
+        run_script(&mut table, "insert or replace 1 z z@example.com\ninsert 3 c c@example.com\n.exit\n");
+
+        assert_eq!(snapshot.num_rows, 2);
+        let output = run_script(&mut snapshot, "select\n.exit\n");
+        assert_eq!(output, "rsql > 1 a a@example.com\n2 b b@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_small_pages_roll_over() {
+        let config = TableConfig { page_size: 512, max_pages: 3, ..TableConfig::default() };
+        assert_eq!(config.rows_per_page(), 1);
+        let mut table = Table::with_config(config);
+
+        let output = run_script(
+            &mut table,
+            "insert 1 a a@example.com\ninsert 2 b b@example.com\ninsert 3 c c@example.com\ninsert 4 d d@example.com\nselect\n.exit\n",
+        );
+        assert!(output.contains("Row not inserted, table full (3/3 rows) '4 d d@example.com'"));
+        assert!(output.ends_with("1 a a@example.com\n2 b b@example.com\n3 c c@example.com\nrsql > "));
+        assert_eq!(table.allocated_pages(), 3);
+
+        let mut image = Vec::new();
+        table.write_to(&mut image).unwrap();
+        assert_eq!(image.len(), rsql::HEADER_SIZE + 3 * 512);
+    }
+
+    #[test]
+    fn test_ids_above_u32_max() {
+        let big = u32::MAX as u64 + 1;
+        let row = Row::new(big, "big".to_string(), "big@example.com".to_string());
+        let mut buffer = [0; ROW_SIZE];
+        serialize(&row, &mut buffer);
+        assert_eq!(deserialize(&buffer).id, big);
+
+        let mut table = Table::new();
+        let output = run_script(
+            &mut table,
+            &format!("insert {} a a@example.com\ninsert {} b b@example.com\nselect\n.exit\n", big, u64::MAX),
+        );
+        assert!(output.ends_with(&format!("{} a a@example.com\n{} b b@example.com\nrsql > ", big, u64::MAX)));
+    }
+
+    #[test]
+    fn test_open_rejects_previous_format_version() {
+        let path = temp_db_path("old_version");
+        let mut header = rsql::FileHeader::for_table(&Table::new()).to_bytes();
+        header[4..6].copy_from_slice(&2u16.to_le_bytes());
+        std::fs::write(&path, header).unwrap();
+
+        assert!(matches!(Table::open(&path), Err(OpenError::UnsupportedVersion(2))));
+    }
+
+    #[test]
+    fn test_auto_increment_ids() {
+        let mut table = Table::new();
+        run_script(
+            &mut table,
+            "insert a a@example.com\ninsert b b@example.com\ninsert 5 c c@example.com\ninsert d d@example.com\n.exit\n",
+        );
+
+        let output = run_script(&mut table, "select\n.exit\n");
+        assert_eq!(
+            output,
+            "rsql > 1 a a@example.com\n2 b b@example.com\n5 c c@example.com\n6 d d@example.com\nrsql > "
+        );
+        assert_eq!(table.next_id, 7);
+    }
+
+    #[test]
+    fn test_next_id_survives_save_and_open() {
+        let path = temp_db_path("next_id");
+        let mut table = Table::new();
+        run_script(&mut table, "insert 41 a a@example.com\n.exit\n");
+        table.save(&path).unwrap();
+
+        let mut reopened = Table::open(&path).unwrap();
+        assert_eq!(reopened.next_id, 42);
+        let output = run_script(&mut reopened, "insert b b@example.com\nselect\n.exit\n");
+        assert!(output.ends_with("41 a a@example.com\n42 b b@example.com\nrsql > "));
+    }
+
+    #[test]
+    fn test_null_email_distinct_from_empty() {
+        let mut table = Table::new();
+        let output = run_script(
+            &mut table,
+            "insert 1 a NULL\ninsert 2 b \"\"\ninsert 3 c \"NULL\"\nselect\n.exit\n",
+        );
+        assert!(output.contains("Invalid email: NULL"));
+        assert!(output.ends_with("1 a NULL\n2 b \nrsql > "), "{}", output);
+
+        let null_row = deserialize(&table.read_slot(0));
+        let empty_row = deserialize(&table.read_slot(1));
+        assert_eq!(null_row.email_str(), None);
+        assert_eq!(empty_row.email_str().as_deref(), Some(""));
+
+        let mut json = Vec::new();
+        execute_select_json(&table, &mut json);
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "[{\"id\":1,\"username\":\"a\",\"email\":null},{\"id\":2,\"username\":\"b\",\"email\":\"\"}]\n"
+        );
+    }
+
+    #[test]
+    fn test_repl_mode_csv() {
+        let mut table = Table::new();
+        let output = run_script(
+            &mut table,
+            "insert 1 a a@example.com\ninsert 2 \"b, jr\" NULL\n.mode csv\nselect\nselect id, email\n.exit\n",
+        );
+        let expected = "rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > rsql > 1,a,a@example.com
+2,\"b, jr\",
+rsql > 1,a@example.com
+2,
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_mode_json() {
+        let mut table = Table::new();
+        let output = run_script(
+            &mut table,
+            "insert 1 a a@example.com\ninsert 2 b NULL\n.mode json\nselect\nselect username\n.mode list\nselect id\n.mode xml\n.exit\n",
+        );
+        let expected = "rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > rsql > [{\"id\":1,\"username\":\"a\",\"email\":\"a@example.com\"},{\"id\":2,\"username\":\"b\",\"email\":null}]
+rsql > [{\"username\":\"a\"},{\"username\":\"b\"}]
+rsql > rsql > 1
+2
+rsql > Usage: .mode list|csv|json|column
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_mode_column() {
+        let mut table = Table::new();
+        let output = run_script(
+            &mut table,
+            "insert 1 al al@example.com\ninsert 10 bartholomew b@x.io\ninsert 100 c NULL\n.mode column\nselect\n.exit\n",
+        );
+        let expected = "rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > rsql > id  | username    | email
+1   | al          | al@example.com
+10  | bartholomew | b@x.io
+100 | c           | NULL
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_open_rejects_page_size_mismatch() {
+        let path = temp_db_path("page_size_mismatch");
+        let mut header = rsql::FileHeader::for_table(&Table::new());
+        header.page_size = 8192;
+        std::fs::write(&path, header.to_bytes()).unwrap();
+
+        let err = Table::open(&path).unwrap_err();
+        assert!(matches!(err, OpenError::LayoutMismatch { file_page_size: 8192, expected: rsql::PAGE_SIZE }));
+        assert_eq!(err.to_string(), "file uses 8192-byte pages, expected 4096");
+
+        let mut header = rsql::FileHeader::for_table(&Table::new());
+        header.row_size = 291;
+        std::fs::write(&path, header.to_bytes()).unwrap();
+        assert!(matches!(
+            Table::open(&path),
+            Err(OpenError::RowSizeMismatch { file_row_size: 291, expected: ROW_SIZE })
+        ));
+    }
+
+    fn three_page_file(name: &str) -> String {
+        let path = temp_db_path(name);
+        let mut table = Table::new();
+        for i in 0..(2 * ROWS_PER_PAGE + 1) as u64 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        table.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_open_loads_pages_lazily() {
+        let path = three_page_file("lazy_pages");
+        let table = Table::open(&path).unwrap();
+        assert_eq!(table.allocated_pages(), 0);
+
+        let row = deserialize(&table.read_slot(2 * ROWS_PER_PAGE));
+        assert_eq!(row.id, 2 * ROWS_PER_PAGE as u64);
+        assert_eq!(table.page_loads(), 1);
+        assert!(!table.is_page_resident(0));
+        assert!(!table.is_page_resident(1));
+        assert!(table.is_page_resident(2));
+
+        deserialize(&table.read_slot(2 * ROWS_PER_PAGE));
+        assert_eq!(table.page_loads(), 1);
+    }
+
+    #[test]
+    fn test_rollback_restores_lazily_loaded_pages() {
+        let path = three_page_file("lazy_rollback");
+        let mut table = Table::open(&path).unwrap();
+
+        run_script(&mut table, "begin\ndelete\ninsert 99 z z@example.com\ndelete\nrollback\n.exit\n");
+
+        assert_eq!(table.num_rows, 2 * ROWS_PER_PAGE + 1);
+        for i in 0..table.num_rows {
+            assert_eq!(deserialize(&table.read_slot(i)).id, i as u64);
+        }
+        drop(table);
+        let reopened = Table::open(&path).unwrap();
+        assert_eq!(reopened.num_rows, 2 * ROWS_PER_PAGE + 1);
+        assert_eq!(deserialize(&reopened.read_slot(0)).id, 0);
+    }
+
+    #[test]
+    fn test_lru_cache_reloads_evicted_pages() {
+        let path = three_page_file("lru_reload");
+        let config = TableConfig { cache_capacity: 1, ..TableConfig::default() };
+        let table = Table::open_with_config(&path, config).unwrap();
+
+        for round in 1..=4 {
+            deserialize(&table.read_slot(0));
+            deserialize(&table.read_slot(ROWS_PER_PAGE));
+            assert_eq!(table.page_loads(), 2 * round);
+            assert_eq!(table.allocated_pages(), 1);
+        }
+    }
+
+    #[test]
+    fn test_lru_cache_writes_back_dirty_pages_on_eviction() {
+        let path = three_page_file("lru_dirty");
+        let config = TableConfig { cache_capacity: 1, ..TableConfig::default() };
+        let mut table = Table::open_with_config(&path, config).unwrap();
+
+        let row = Row::new(500, "moved".to_string(), "moved@example.com".to_string());
+        serialize(&row, table.row_slot(0));
+        deserialize(&table.read_slot(ROWS_PER_PAGE));
+        assert!(!table.is_page_resident(0));
+        assert_eq!(deserialize(&table.read_slot(0)).id, 500);
+
+        table.flush_dirty().unwrap();
+        drop(table);
+        let reopened = Table::open(&path).unwrap();
+        assert_eq!(deserialize(&reopened.read_slot(0)).username_str().unwrap(), "moved");
+        assert_eq!(deserialize(&reopened.read_slot(2 * ROWS_PER_PAGE)).id, 2 * ROWS_PER_PAGE as u64);
+    }
+
+    #[test]
+    fn test_dump_replays_into_new_table() {
+        let mut table = Table::new();
+        let setup = concat!(
+            "insert 1 alice alice@example.com\n",
+            "insert 2 \"doe john\" john@example.com\n",
+            "insert 3 \"say \\\"hi\\\"\" NULL\n",
+            "insert 4 \"NULL\" \"\"\n",
+            ".exit\n",
+        );
+        run_script(&mut table, setup);
+        assert_eq!(table.num_rows, 4);
+
+        let mut dumped = Vec::new();
+        dump(&table, &mut dumped).unwrap();
+        let dumped = String::from_utf8(dumped).unwrap();
+        assert_eq!(dumped.lines().next(), Some("insert 1 alice alice@example.com"));
+
+        let mut rebuilt = Table::new();
+        run_script(&mut rebuilt, &format!("{}.exit\n", dumped));
+        assert_eq!(rebuilt.num_rows, table.num_rows);
+        for i in 0..table.num_rows {
+            assert_eq!(deserialize(&rebuilt.read_slot(i)), deserialize(&table.read_slot(i)));
+        }
+        assert_eq!(run_script(&mut rebuilt, ".dump\n.exit\n"), format!("rsql > {}rsql > ", dumped));
+    }
+
+    #[test]
+    fn test_repl_verbose() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".verbose on\ninsert 1 a a@example.com\nselect\n.verbose off\ninsert 2 b b@example.com\n.verbose maybe\n.exit\n",
+        );
+
+        let expected = "rsql > rsql > inserting...
+1 row inserted
+Inserted id 1
+rows: 1
+rsql > selecting...
+1 a a@example.com
+rows: 1
+rsql > rsql > 1 row inserted
+rsql > Usage: .verbose on|off
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_drop_empties_backing_file() {
+        let path = temp_db_path("drop");
+        let mut table = Table::open(&path).unwrap();
+
+        let output = run_script(&mut table, "insert 1 a a@example.com\ninsert b b@example.com\n.drop\nselect\n.exit\n");
+
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > 1 row inserted\nrsql > rsql > rsql > ");
+        assert_eq!(table.num_rows, 0);
+        assert_eq!(table.next_id, 1);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        assert_eq!(std::fs::metadata(wal_path(&path)).unwrap().len(), 0);
+        drop(table);
+
+        let reopened = Table::open(&path).unwrap();
+        assert_eq!(reopened.num_rows, 0);
+    }
+
+    #[test]
+    fn test_insert_multiple_tuples() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert (1,a,a@b.com), (2, \"b c\", NULL)\nselect\n.exit\n");
+
+        assert_eq!(output, "rsql > 2 rows inserted\nrsql > 1 a a@b.com\n2 b c NULL\nrsql > ");
+        assert_eq!(table.num_rows, 2);
+    }
+
+    #[test]
+    fn test_insert_tuples_stop_at_duplicate() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert 2 x x@b.com\ninsert (1,a,a@b.com),(2,b,b@c.com),(3,c,c@d.com)\ninsert (4,d,d@e.com)(5,e,e@f.com)\n.exit\n",
+        );
+
+        let expected = "rsql > 1 row inserted
+rsql > 1 of 3 rows inserted
+Error: Duplicate key.
+rsql > Syntax Error in 'insert (4,d,d@e.com)(5,e,e@f.com)'
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(table.num_rows, 2);
+        assert_eq!(deserialize(&table.read_slot(1)).id, 1);
+        assert!(!table.contains_id(3));
+    }
+
+    #[test]
+    fn test_insert_row_returns_index() {
+        let mut table = Table::new();
+        execute_insert(&mut table, &Row::new(1, "a".to_string(), "a@example.com".to_string()));
+
+        let previous = table.num_rows;
+        let index = table.insert_row(Row::new(7, "b".to_string(), "b@example.com".to_string())).unwrap();
+
+        assert_eq!(index, previous);
+        assert_eq!(table.num_rows, previous + 1);
+        assert_eq!(deserialize(&table.read_slot(index)).id, 7);
+        assert!(matches!(
+            table.insert_row(Row::new(7, "c".to_string(), "c@example.com".to_string())),
+            Err(DbError::DuplicateKey)
+        ));
+    }
+
+    #[test]
+    fn test_get_row_by_index() {
+        let mut table = Table::new();
+        for id in [5, 3] {
+            execute_insert(&mut table, &Row::new(id, format!("user{}", id), format!("user{}@example.com", id)));
+        }
+
+        assert_eq!(table.get(1).map(|row| row.id), Some(3));
+        assert_eq!(table.get(0), Some(Row::new(5, "user5".to_string(), "user5@example.com".to_string())));
+        assert_eq!(table.get(2), None);
+        assert_eq!(table.get(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_select_where_between() {
+        let mut table = Table::new();
+        for id in 1..=12 {
+            execute_insert(&mut table, &Row::new(id, format!("u{}", id), format!("u{}@example.com", id)));
+        }
+
+        let output = run_script(&mut table, "select id where id between 5 and 7\n.exit\n");
+        assert_eq!(output, "rsql > 5\n6\n7\nrsql > ");
+
+        let output = run_script(&mut table, "select where id between 20 and 30\n.exit\n");
+        assert_eq!(output, "rsql > rsql > ");
+
+        let output = run_script(&mut table, "select where id between 10 and 5\n.exit\n");
+        assert_eq!(output, "rsql > rsql > ");
+
+        let output = run_script(&mut table, "select where username between 1 and 2\n.exit\n");
+        assert_eq!(output, "rsql > Syntax Error in 'select where username between 1 and 2': cannot apply 'between' to 'username'\nrsql > ");
+    }
+
+    #[test]
+    fn test_select_distinct_skips_repeated_ids() {
+        let mut table = Table::new();
+        execute_insert(&mut table, &Row::new(1, "first".to_string(), "first@example.com".to_string()));
+        execute_insert(&mut table, &Row::new(2, "other".to_string(), "other@example.com".to_string()));
+        // bypass the uniqueness check to plant a duplicate id
+        serialize(&Row::new(1, "second".to_string(), "second@example.com".to_string()), table.row_slot(2));
+        table.num_rows = 3;
+
+        let output = run_script(&mut table, "select distinct\nselect distinct username\n.exit\n");
+
+        assert_eq!(output, "rsql > 1 first first@example.com\n2 other other@example.com\nrsql > first\nother\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_reports_inserted_id() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, ".verbose on\ninsert 41 a a@example.com\ninsert b b@example.com\n.exit\n");
+
+        let inserted: Vec<&str> = output.lines().filter(|line| line.starts_with("Inserted id")).collect();
+        assert_eq!(inserted, ["Inserted id 41", "Inserted id 42"]);
+    }
+
+    #[test]
+    fn test_read_runs_script_file() {
+        let script = temp_db_path("read_script");
+        std::fs::write(&script, "insert 1 a a@example.com\ninsert 1 dup dup@example.com\nbogus\n\ninsert 2 b b@example.com\n").unwrap();
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, &format!(".read {}\n.exit\n", script));
+
+        let expected = "rsql > 1 row inserted
+line 2: Error: Duplicate key.
+line 3: Unrecognized keyword at start of 'bogus'
+1 row inserted
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(table.num_rows, 2);
+        assert_eq!(table.get(1).map(|row| row.id), Some(2));
+
+        let mut table = Table::new();
+        let output = run_script(&mut table, &format!(".bail on\n.read {}\n.exit\n", script));
+        assert_eq!(output, "rsql > rsql > 1 row inserted\nline 2: Error: Duplicate key.\nrsql > ");
+        assert_eq!(table.num_rows, 1);
+    }
+
+    #[test]
+    fn test_run_line_keeps_state_between_calls() {
+        let mut table = Table::new();
+        let mut state = ReplState::default();
+        let mut output = Vec::new();
+
+        assert_eq!(run_line("insert 1 a a@example.com", &mut table, &mut state, &mut output), LineOutcome::Ok);
+        assert_eq!(run_line(".mode csv", &mut table, &mut state, &mut output), LineOutcome::Ok);
+        assert_eq!(run_line("select", &mut table, &mut state, &mut output), LineOutcome::Ok);
+        assert_eq!(run_line("bogus", &mut table, &mut state, &mut output), LineOutcome::Failed);
+        assert_eq!(run_line(".exit", &mut table, &mut state, &mut output), LineOutcome::Exit);
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "1 row inserted\n1,a,a@example.com\nUnrecognized keyword at start of 'bogus'\n");
+    }
+
+    #[test]
+    fn test_output_redirects_select_results() {
+        let path = temp_db_path("output_redirect");
+        let mut table = Table::new();
+
+        let script = format!(
+            "insert 1 a a@example.com\n.output {}\nselect\ninsert 1 b b@example.com\n.output stdout\nselect id\n.exit\n",
+            path
+        );
+        let output = run_script(&mut table, &script);
+
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > rsql > rsql > Error: Duplicate key.\nrsql > rsql > 1\nrsql > ");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1 a a@example.com\n");
+    }
+
+    #[test]
+    fn test_select_where_username_equals() {
+        let mut table = Table::new();
+        let setup = "insert 1 alice a1@example.com\ninsert 2 bob b@example.com\ninsert 3 alice a3@example.com\ninsert 4 NULL n@example.com\n";
+
+        let output = run_script(&mut table, &format!("{}select id where username = alice\nselect where username = carol\n.exit\n", setup));
+
+        assert!(output.ends_with("rsql > 1\n3\nrsql > rsql > "));
+        assert_eq!(table.find_username("alice"), [0, 2]);
+        assert_eq!(table.find_username("NULL"), Vec::<usize>::new());
+
+        run_script(&mut table, "insert 5 alice a5@example.com\n.exit\n");
+        assert_eq!(table.find_username("alice"), [0, 2, 4]);
+    }
+
+    #[test]
+    fn test_username_index_follows_delete() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 1 alice a@example.com\ninsert 2 bob b@example.com\n.exit\n");
+        assert_eq!(table.find_username("bob"), [1]);
+
+        let output = run_script(&mut table, "delete\ninsert 7 bob b@example.com\nselect where username = alice\nselect id where username = bob\n.exit\n");
+
+        assert_eq!(output, "rsql > 2 rows deleted\nrsql > 1 row inserted\nrsql > rsql > 7\nrsql > ");
+        assert_eq!(table.find_username("alice"), Vec::<usize>::new());
+        assert_eq!(table.find_username("bob"), [0]);
+    }
+
+    #[test]
+    fn test_vacuum_packs_rows_after_deletes() {
+        let mut table = Table::new();
+        for id in 1..=10 {
+            execute_insert(&mut table, &Row::new(id, format!("user{}", id), format!("user{}@example.com", id)));
+        }
+
+        let deletes: String = (2..=10).step_by(2).map(|id| format!("delete where id = {}\n", id)).collect();
+        let output = run_script(&mut table, &format!("{}select count\n.exit\n", deletes));
+        assert!(output.ends_with("1 row deleted\nrsql > 5\nrsql > "), "unexpected output {:?}", output);
+        assert_eq!(table.num_rows, 10);
+        assert_eq!(table.get(1), None);
+
+        run_script(&mut table, ".vacuum\n.exit\n");
+
+        assert_eq!(table.num_rows, 5);
+        let ids: Vec<u64> = (0..table.num_rows).map(|i| table.get(i).unwrap().id).collect();
+        assert_eq!(ids, [1, 3, 5, 7, 9]);
+        assert!(table.contains_id(9));
+        assert!(!table.contains_id(10));
+    }
+
+    #[test]
+    fn test_vacuum_frees_emptied_pages() {
+        let path = three_page_file("vacuum_pages");
+        let mut table = Table::open(&path).unwrap();
+
+        let output = run_script(&mut table, &format!("delete where id between {} and 1000\n.exit\n", ROWS_PER_PAGE));
+        assert_eq!(output, format!("rsql > {} rows deleted\nrsql > ", ROWS_PER_PAGE + 1));
+        run_script(&mut table, ".vacuum\n.exit\n");
+
+        assert_eq!(table.num_rows, ROWS_PER_PAGE);
+        assert!(!table.is_page_resident(1));
+        assert!(!table.is_page_resident(2));
+        drop(table);
+        let reopened = Table::open(&path).unwrap();
+        assert_eq!(reopened.num_rows, ROWS_PER_PAGE);
+        assert_eq!(reopened.live_rows(), ROWS_PER_PAGE);
+    }
+
+    #[test]
+    fn test_open_error_names_the_path() {
+        let path = std::env::temp_dir().join("rsql_missing_dir").join("nested").join("db.rsql");
+
+        let err = Table::open(&path).unwrap_err();
+
+        assert!(matches!(&err, OpenError::Io(io_err) if io_err.kind() == io::ErrorKind::NotFound));
+        assert!(err.to_string().contains(&path.display().to_string()), "message was {}", err);
+        let err = Table::new().save(&path).unwrap_err();
+        assert!(err.to_string().contains(&path.display().to_string()), "message was {}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_non_utf8_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let name = std::ffi::OsStr::from_bytes(b"rsql_non_utf8_\xff.db");
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(wal_path(&path));
+
+        let mut table = Table::open(&path).unwrap();
+        execute_insert(&mut table, &Row::new(1, "a".to_string(), "a@example.com".to_string()));
+        table.flush_dirty().unwrap();
+        drop(table);
+
+        assert_eq!(Table::open(&path).unwrap().num_rows, 1);
+        assert!(wal_path(&path).exists());
+    }
+
+    #[test]
+    fn test_syntax_error_reports_column() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert 1 alice\ninsert abc alice a@example.com\ninsert  or replace x b b@example.com\n.exit\n");
+
+        let expected = "rsql > Syntax error at column 15: got id=1, username=alice, missing email
+rsql > Syntax error at column 8: expected id
+rsql > Syntax error at column 20: expected id
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(table.num_rows, 0);
+    }
+
+    #[test]
+    fn test_parse_insert_statements() {
+        let statement = parse(r#"insert 3 "Jo Ann" NULL"#).unwrap();
+        assert_eq!(
+            statement.statement_type(),
+            &StatementType::Insert {
+                rows: vec![Row::try_new_nullable(3, Some("Jo Ann".to_string()), None).unwrap()],
+                replace: false,
+                auto_id: false,
+            }
+        );
+
+        let statement = parse("insert or replace 3 a a@example.com").unwrap();
+        assert!(matches!(statement.statement_type(), StatementType::Insert { replace: true, auto_id: false, .. }));
+
+        let statement = parse("insert a a@example.com").unwrap();
+        assert!(matches!(statement.statement_type(), StatementType::Insert { replace: false, auto_id: true, .. }));
+
+        let statement = parse("insert (1,a,a@example.com),(2,b,b@example.com)").unwrap();
+        let StatementType::Insert { rows, .. } = statement.statement_type() else {
+            panic!("expected an insert");
+        };
+        assert_eq!(rows.iter().map(|row| row.id).collect::<Vec<_>>(), [1, 2]);
+
+        assert_eq!(
+            parse("insert 1 a").unwrap_err(),
+            ParseError::MissingFields {
+                column: 11,
+                parsed: vec![("id", "1".to_string()), ("username", "a".to_string())],
+                missing: vec!["email"],
+            }
+        );
+        assert_eq!(parse("insert 1 a nope").unwrap_err(), ParseError::InvalidEmail("nope".to_string()));
+    }
+
+    #[test]
+    fn test_parse_select_statements() {
+        let statement = parse("select id, email where id between 2 and 4 order by id desc").unwrap();
+        assert_eq!(
+            statement.statement_type(),
+            &StatementType::Select(Select {
+                columns: vec![Column::Id, Column::Email],
+                filter: Some(Filter::Between { low: 2, high: 4 }),
+                order_by: Some(OrderBy { column: Column::Id, descending: true }),
+                ..Select::default()
+            })
+        );
+
+        let select = |input: &str| match parse(input).unwrap().statement_type() {
+            StatementType::Select(select) => select.clone(),
+            other => panic!("expected a select, got {:?}", other),
+        };
+        assert!(select("select count").count);
+        assert_eq!(select("select max").aggregate, Some(Aggregate::Max));
+        assert!(select("select distinct").distinct);
+        assert_eq!(select("select where username = bob").filter, Some(Filter::UsernameEquals("bob".to_string())));
+        assert_eq!(parse("select nope").unwrap_err(), ParseError::Syntax(Some("unknown column 'nope'".to_string())));
+    }
+
+    #[test]
+    fn test_parse_other_statements() {
+        assert_eq!(parse("delete").unwrap().statement_type(), &StatementType::Delete { filter: None });
+        assert_eq!(
+            parse("delete where id = 4").unwrap().statement_type(),
+            &StatementType::Delete { filter: Some(Filter::IdEquals(4)) }
+        );
+
+        assert_eq!(parse("begin").unwrap().statement_type(), &StatementType::Begin);
+        assert_eq!(parse("commit").unwrap().statement_type(), &StatementType::Commit);
+        assert_eq!(parse("rollback").unwrap().statement_type(), &StatementType::Rollback);
+        assert_eq!(parse("begin now").unwrap_err(), ParseError::Syntax(None));
+        assert_eq!(parse("upsert 1").unwrap_err(), ParseError::Unrecognized);
+    }
+
+    #[test]
+    fn test_insert_with_repeated_whitespace() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert  1  a  a@b.com\ninsert\t2 \t\tb\t  b@c.com  \nselect\n.exit\n");
+
+        assert_eq!(
+            output,
+            "rsql > 1 row inserted\nrsql > 1 row inserted\nrsql > 1 a a@b.com\n2 b b@c.com\nrsql > "
+        );
+    }
+
+    #[test]
+    fn test_repl_echo() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".echo on\ninsert 1 a a@example.com\nselect\n.echo off\nselect\n.echo loud\n.exit\n",
+        );
+
+        let expected = "rsql > rsql > insert 1 a a@example.com
+1 row inserted
+rsql > select
+1 a a@example.com
+rsql > .echo off
+rsql > 1 a a@example.com
+rsql > Usage: .echo on|off
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_syntax_error_lists_missing_fields() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert 1\ninsert\n.exit\n");
+
+        let expected = "rsql > Syntax error at column 9: got id=1, missing username and email
+rsql > Syntax error at column 7: missing id, username and email
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(
+            parse("insert 1").unwrap_err(),
+            ParseError::MissingFields { column: 9, parsed: vec![("id", "1".to_string())], missing: vec!["username", "email"] }
+        );
+    }
+
+    #[test]
+    fn test_set_max_rows_caps_inserts() {
+        let mut table = Table::new();
+        table.set_max_rows(Some(3));
+        for i in 1..=3 {
+            let result = execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+            assert!(matches!(result, ExecuteResult::Inserted { id } if id == i));
+        }
+
+        let result = execute_insert(&mut table, &Row::new(4, "user4".to_string(), "user4@example.com".to_string()));
+
+        assert!(matches!(result, ExecuteResult::TableFull));
+        assert_eq!(table.num_rows, 3);
+        assert_eq!(table.max_rows(), 3);
+        table.set_max_rows(None);
+        assert_eq!(table.max_rows(), table.config().max_rows());
+        assert!(matches!(
+            execute_insert(&mut table, &Row::new(4, "user4".to_string(), "user4@example.com".to_string())),
+            ExecuteResult::Inserted { id: 4 }
+        ));
+    }
+
+    #[test]
+    fn test_select_order_by_username() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert 1 charlie c@example.com\ninsert 2 alice a@example.com\ninsert 3 bob b@example.com\ninsert 4 alice a2@example.com\nselect order by username\nselect id order by username desc\n.exit\n",
+        );
+
+        let expected = "rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > 2 alice a@example.com
+4 alice a2@example.com
+3 bob b@example.com
+1 charlie c@example.com
+rsql > 1
+3
+2
+4
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_memory_usage_counts_allocated_pages() {
+        let mut table = Table::new();
+        assert_eq!(table.memory_usage(), 0);
+        for i in 1..=ROWS_PER_PAGE as u64 + 1 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+
+        assert_eq!(table.memory_usage(), 2 * PAGE_SIZE);
+        assert_eq!(run_script(&mut table, ".mem\n.exit\n"), format!("rsql > memory used: {} bytes\nrsql > ", 2 * PAGE_SIZE));
+        table.find_username("user1");
+        assert!(table.memory_usage() > 2 * PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_open_readonly_refuses_writes() {
+        let path = temp_db_path("readonly");
+        let mut table = Table::open(&path).unwrap();
+        execute_insert(&mut table, &Row::new(1, "alice".to_string(), "alice@example.com".to_string()));
+        table.flush_dirty().unwrap();
+        drop(table);
+        let before = std::fs::read(&path).unwrap();
+
+        let mut readonly = Table::open_readonly(&path).unwrap();
+        assert!(readonly.is_read_only());
+        assert!(matches!(
+            execute_insert(&mut readonly, &Row::new(2, "bob".to_string(), "bob@example.com".to_string())),
+            ExecuteResult::ReadOnly
+        ));
+        let output = run_script(&mut readonly, "insert 2 bob bob@example.com\ndelete\n.drop\nselect\n.exit\n");
+        readonly.flush_dirty().unwrap();
+
+        let expected = "rsql > Error: database is read-only
+rsql > Error: database is read-only
+rsql > Error: database is read-only
+rsql > 1 alice alice@example.com
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(wal_path(&path)).ok();
+    }
+
+    #[test]
+    fn test_open_locks_the_file() {
+        let path = temp_db_path("lock");
+        let table = Table::open(&path).unwrap();
+
+        assert!(matches!(Table::open(&path), Err(OpenError::Locked(locked)) if locked == std::path::Path::new(&path)));
+        assert!(matches!(Table::open_readonly(&path), Err(OpenError::Locked(_))));
+        drop(table);
+
+        let first = Table::open_readonly(&path).unwrap();
+        let second = Table::open_readonly(&path).unwrap();
+        assert!(matches!(Table::open(&path), Err(OpenError::Locked(_))));
+        drop((first, second));
+        assert!(Table::open(&path).is_ok());
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(wal_path(&path)).ok();
+    }
+
+    #[test]
+    fn test_backup_copies_rows() {
+        let path = temp_db_path("backup_source");
+        let backup = temp_db_path("backup_copy");
+        let memory_backup = temp_db_path("backup_memory");
+        let mut table = Table::open(&path).unwrap();
+        let mut in_memory = Table::new();
+        for i in 1..=ROWS_PER_PAGE as u64 + 2 {
+            let row = Row::new(i, format!("user{}", i), format!("user{}@example.com", i));
+            execute_insert(&mut table, &row);
+            execute_insert(&mut in_memory, &row);
+        }
+
+        assert_eq!(run_script(&mut table, &format!(".backup {}\n.exit\n", backup)), "rsql > rsql > ");
+        in_memory.backup(&memory_backup).unwrap();
+
+        for copy in [&backup, &memory_backup] {
+            let restored = Table::open(copy).unwrap();
+            assert_eq!(restored.num_rows, table.num_rows);
+            for i in 0..table.num_rows {
+                assert_eq!(restored.get(i), table.get(i));
+            }
+            assert!(!std::path::Path::new(&format!("{}.tmp", copy)).exists());
+        }
+        drop(table);
+        for file in [&path, &backup, &memory_backup] {
+            std::fs::remove_file(file).ok();
+            std::fs::remove_file(wal_path(file)).ok();
+        }
+    }
+
+    #[test]
+    fn test_write_all_round_trips_rows() {
+        let mut table = Table::new();
+        for i in 1..=ROWS_PER_PAGE as u64 + 3 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        run_script(&mut table, "delete where id = 2\n.exit\n");
+        let mut bytes = Vec::new();
+
+        table.write_all(&mut bytes).unwrap();
+
+        assert_eq!(bytes.len(), 8 + table.live_rows() * ROW_SIZE);
+        let small_pages = TableConfig { page_size: 4 + 2 * ROW_SIZE, max_pages: 16, ..TableConfig::default() };
+        for restored in [
+            Table::read_all(&mut bytes.as_slice()).unwrap(),
+            Table::read_all_with_config(&mut bytes.as_slice(), small_pages).unwrap(),
+        ] {
+            assert_eq!(restored.num_rows, table.live_rows());
+            assert_eq!(restored.next_id, table.next_id);
+            let expected: Vec<Row> = table.live_indices().map(|i| table.get(i).unwrap()).collect();
+            let rows: Vec<Row> = (0..restored.num_rows).map(|i| restored.get(i).unwrap()).collect();
+            assert_eq!(rows, expected);
+        }
+        assert!(Table::read_all(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_repl_width_truncates_column_mode() {
+        let mut table = Table::new();
+        let output = run_script(
+            &mut table,
+            "insert 1 al al@example.com\ninsert 10 bartholomew bartholomew@example.com\n.mode column\n.width 4 0 10\nselect\n.width\nselect email\n.width 1 2 3 4\n.exit\n",
+        );
+        let expected = "rsql > 1 row inserted
+rsql > 1 row inserted
+rsql > rsql > rsql > id   | username    | email
+1    | al          | al@exampl\u{2026}
+10   | bartholomew | bartholom\u{2026}
+rsql > rsql > email
+al@example.com
+bartholomew@example.com
+rsql > Usage: .width [id] [username] [email]
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_update_only_named_columns() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert 1 alice alice@example.com\nupdate 1 set email=alice@new.org\nselect\nupdate 1 set username=\"Alice B\"\nselect\nupdate 1 set username = al, email=NULL\nselect\nupdate 9 set email=x@y.z\n.exit\n",
+        );
+
+        let expected = "rsql > 1 row inserted
+rsql > 1 row updated
+rsql > 1 alice alice@new.org
+rsql > 1 row updated
+rsql > 1 Alice B alice@new.org
+rsql > 1 row updated
+rsql > 1 al NULL
+rsql > 0 rows updated
+rsql > ";
+        assert_eq!(output, expected);
+        assert_eq!(table.num_rows, 1);
+    }
+
+    #[test]
+    fn test_parse_update_statements() {
+        let statement = parse("update 3 set email = a@b.c, username=\"x y\"").unwrap();
+        assert_eq!(
+            statement.statement_type(),
+            &StatementType::Update {
+                id: 3,
+                assignments: vec![(Column::Email, Some("a@b.c".to_string())), (Column::Username, Some("x y".to_string()))],
+            }
+        );
+
+        assert_eq!(parse("update 3 set email=nope").unwrap_err(), ParseError::InvalidEmail("nope".to_string()));
+        assert_eq!(parse("update 3 set id=4").unwrap_err(), ParseError::Syntax(Some("cannot set 'id'".to_string())));
+        assert_eq!(parse(&format!("update 3 set username={}", "a".repeat(33))).unwrap_err(), ParseError::StringTooLong);
+        assert!(matches!(parse("update 3 email=a@b.c"), Err(ParseError::Syntax(Some(_)))));
+        assert!(matches!(parse("update x set email=a@b.c"), Err(ParseError::Syntax(Some(_)))));
+    }
+
+    #[test]
+    fn test_row_display() {
+        let row = Row::new(7, "alice".to_string(), "alice@example.com".to_string());
+        let nullable = Row::try_new_nullable(8, Some("bob".to_string()), None).unwrap();
+
+        assert_eq!(format!("{}", row), "7 alice alice@example.com");
+        assert_eq!(format!("[{}]", nullable), "[8 bob NULL]");
+        assert_eq!(row.to_string(), format!("{}", row));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_row_serde_json_round_trip() {
+        let row = Row::try_new_nullable(5, Some("alice".to_string()), None).unwrap();
+
+        let json = serde_json::to_string(&row).unwrap();
+
+        assert_eq!(json, r#"{"id":5,"username":"alice","email":null}"#);
+        assert_eq!(serde_json::from_str::<Row>(&json).unwrap(), row);
+        let too_long = format!(r#"{{"id":1,"username":"{}","email":null}}"#, "a".repeat(33));
+        assert!(serde_json::from_str::<Row>(&too_long).is_err());
+    }
+
+    #[test]
+    fn test_repl_changes_counts_last_write() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".changes\ninsert (1,a,a@x.io),(2,b,b@x.io),(3,c,c@x.io)\n.changes\ndelete where id between 1 and 2\nselect\n.changes\nupdate 3 set email=c@y.io\n.changes\n.exit\n",
+        );
+
+        let expected = "rsql > 0
+rsql > 3 rows inserted
+rsql > 3
+rsql > 2 rows deleted
+rsql > 3 c c@x.io
+rsql > 2
+rsql > 1 row updated
+rsql > 1
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_seed_inserts_generated_rows() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, ".seed 50\n.seed lots\n.exit\n");
+
+        assert_eq!(output, "rsql > Seeded 50 rows.\nrsql > Usage: .seed <count>\nrsql > ");
+        assert_eq!(table.num_rows, 50);
+        let ids: Vec<u64> = (0..table.num_rows).map(|i| table.get(i).unwrap().id).collect();
+        assert_eq!(ids, (1..=50).collect::<Vec<u64>>());
+        assert_eq!(table.get(49).unwrap().to_string(), "50 user50 user50@example.com");
+    }
+
+    #[test]
+    fn test_seed_stops_when_table_full() {
+        let mut table = Table::new();
+        table.set_max_rows(Some(3));
+
+        let output = run_script(&mut table, ".seed 5\n.exit\n");
+
+        assert_eq!(output, "rsql > Error: Table full.\nSeeded 3 of 5 rows.\nrsql > ");
+        assert_eq!(table.num_rows, 3);
+    }
+
+    #[test]
+    fn test_run_line_ignores_blank_input() {
+        let mut table = Table::new();
+        let mut state = ReplState::default();
+        let mut output = Vec::new();
+
+        for blank in ["", " ", "\n", " \t \r\n"] {
+            assert_eq!(run_line(blank, &mut table, &mut state, &mut output), LineOutcome::Ok);
+        }
+        assert_eq!(run_line("  .exit\n", &mut table, &mut state, &mut output), LineOutcome::Exit);
+
+        assert!(output.is_empty());
+        assert_eq!(run_script(&mut table, "\n   \n.exit\n"), "rsql > rsql > rsql > ");
+    }
+
+    #[test]
+    fn test_repl_limit_caps_select() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 5\n.limit 2\nselect id\nselect id order by id desc limit 3\n.limit off\nselect id where id between 2 and 5 limit 1\nselect count\n.limit 0\nselect id\n.limit some\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 5 rows.
+rsql > rsql > 1
+2
+rsql > 5
+4
+3
+rsql > rsql > 2
+rsql > 5
+rsql > rsql > 1
+2
+3
+4
+5
+rsql > Usage: .limit <n>|off
+rsql > ";
+        assert_eq!(output, expected);
+        assert!(matches!(
+            parse("select limit 4").unwrap().statement_type(),
+            StatementType::Select(select) if select.limit == Some(4)
+        ));
+        assert!(parse("select limit 4 where id = 1").is_err());
+    }
+
+    #[test]
+    fn test_select_rows_returns_matches() {
+        let mut table = Table::new();
+        run_script(&mut table, ".seed 6\ndelete where id = 4\n.exit\n");
+
+        let rows = table.select_rows(Some(&Filter::IdEquals(3)));
+        assert_eq!(rows, [Row::new(3, "user3".to_string(), "user3@example.com".to_string())]);
+
+        let ids: Vec<u64> = table.select_rows(Some(&Filter::Between { low: 2, high: 5 })).iter().map(|row| row.id).collect();
+        assert_eq!(ids, [2, 3, 5]);
+        assert_eq!(table.select_rows(None).len(), 5);
+        assert_eq!(table.select_rows(Some(&Filter::UsernameEquals("user5".to_string()))).len(), 1);
+        assert!(table.select_rows(Some(&Filter::IdEquals(4))).is_empty());
+    }
+
+    #[test]
+    fn test_slot_index_overflow_is_an_error() {
+        let mut table = Table::new();
+        execute_insert(&mut table, &Row::new(1, "a".to_string(), "a@example.com".to_string()));
+
+        assert!(matches!(table.try_read_slot(usize::MAX), Err(DbError::IndexOverflow(usize::MAX))));
+        assert!(matches!(table.try_row_slot(usize::MAX), Err(DbError::IndexOverflow(usize::MAX))));
+        assert!(matches!(table.try_read_slot(table.max_rows()), Err(DbError::IndexOverflow(_))));
+        assert_eq!(table.try_read_slot(0).unwrap(), table.read_slot(0));
+        assert_eq!(table.get(usize::MAX), None);
+        assert_eq!(table.num_rows, 1);
+    }
+
+    #[test]
+    fn test_repl_scan_limit_stops_select() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 20\n.scanlimit 5\nselect where id between 100 and 200\nselect id where id between 1 and 10\n.scanlimit off\nselect where id = 100\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 20 rows.
+rsql > rsql > scan limit reached after 5 rows
+rsql > 1
+2
+3
+4
+5
+scan limit reached after 5 rows
+rsql > rsql > rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_compact_pages_frees_trailing_pages() {
+        let mut table = Table::new();
+        for i in 1..=ROWS_PER_PAGE as u64 + 2 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        assert!(table.is_page_resident(1));
+        table.num_rows = 3;
+
+        table.compact_pages();
+
+        assert!(table.is_page_resident(0));
+        assert!(!table.is_page_resident(1));
+        assert_eq!(table.allocated_pages(), 1);
+        let ids: Vec<u64> = table.select_rows(None).iter().map(|row| row.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_rows_sort_by_id_then_username() {
+        let mut rows: Vec<Row> = Vec::from([
+            Row::new(2, "b".to_string(), "b@example.com".to_string()),
+            Row::new(1, "z".to_string(), "z@example.com".to_string()),
+            Row::new(2, "a".to_string(), "b@example.com".to_string()),
+            Row::new(2, "a".to_string(), "a@example.com".to_string()),
+        ]);
+
+        rows.sort();
+
+        let keys: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+        assert_eq!(keys, ["1 z z@example.com", "2 a a@example.com", "2 a b@example.com", "2 b b@example.com"]);
+        assert_eq!(rows.iter().max().unwrap().to_string(), "2 b b@example.com");
+    }
+
+    #[test]
+    fn test_rows_in_hash_set() {
+        let mut rows = std::collections::HashSet::new();
+
+        assert!(rows.insert(Row::new(1, "a".to_string(), "a@example.com".to_string())));
+        assert!(!rows.insert(Row::new(1, "a".to_string(), "a@example.com".to_string())));
+        assert!(rows.insert(Row::new(1, "a".to_string(), "other@example.com".to_string())));
+        assert!(rows.insert(Row::try_new_nullable(1, Some("a".to_string()), None).unwrap()));
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows.contains(&Row::new(1, "a".to_string(), "other@example.com".to_string())));
+    }
+
+    #[test]
+    fn test_repl_pager_pauses_between_screenfuls() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 5\n.pager on 2\nselect id\n\n\nselect id\nq\n.pager off\nselect id where id = 5\n.pager on 0\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 5 rows.
+rsql > rsql > 1
+2
+-- more --3
+4
+-- more --5
+rsql > 1
+2
+-- more --rsql > rsql > 5
+rsql > Usage: .pager on [lines]|off
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_explain_insert_does_not_insert() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 3\nexplain insert 10 a a@example.com\nexplain insert c c@example.com\n.changes\nselect count\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 3 rows.
+rsql > insert 1 row into table
+estimated rows scanned: 3
+rsql > insert 1 row into table
+id: 4 (next id)
+estimated rows scanned: 3
+rsql > 0
+rsql > 3
+rsql > ";
+        assert_eq!(output, expected);
+        assert!(parse("explain insert 10 a a@example.com").unwrap().is_explain());
+        assert!(!parse("insert 10 a a@example.com").unwrap().is_explain());
+    }
+
+    #[test]
+    fn test_explain_filtered_select() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 5\nexplain select where id = 2\nexplain select where username = user2\nexplain select where id between 1 and 3 order by id desc limit 2\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 5 rows.
+rsql > select from table
+filter: id = 2 (full scan)
+estimated rows scanned: 5
+rsql > select from table
+filter: username = user2 (username index)
+estimated rows scanned: 1
+rsql > select from table
+filter: id between 1 and 3 (full scan)
+order by: id desc
+limit: 2
+estimated rows scanned: 5
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_explain_reports_columns_past_prefix() {
+        assert_eq!(
+            parse("explain insert 1 a").unwrap_err(),
+            ParseError::MissingFields {
+                column: 19,
+                parsed: vec![("id", "1".to_string()), ("username", "a".to_string())],
+                missing: vec!["email"],
+            }
+        );
+    }
+
+    #[test]
+    fn test_open_recovers_truncated_last_page() {
+        let path = temp_db_path("truncated_page");
+        let rows = 2 * ROWS_PER_PAGE + 3;
+        let mut table = Table::new();
+        for i in 0..rows as u64 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        table.save(&path).unwrap();
+        drop(table);
+        // cut the last page off partway through its third row
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(page_offset(2) + 2 * ROW_SIZE as u64 + 10).unwrap();
+        drop(file);
+
+        let mut table = Table::open(&path).unwrap();
+        assert_eq!(
+            table.truncated_page(),
+            Some(&TruncatedPage { page: 2, bytes: 2 * ROW_SIZE + 10, rows_recovered: rows - 1, rows_expected: rows })
+        );
+        assert_eq!(table.num_rows, rows - 1);
+        for i in 0..rows - 1 {
+            assert_eq!(table.get(i), Some(Row::new(i as u64, format!("user{}", i), format!("user{}@example.com", i))));
+        }
+        assert_eq!(table.get(rows - 1), None);
+
+        // the recovered page is written back whole
+        table.flush_dirty().unwrap();
+        drop(table);
+        let table = Table::open(&path).unwrap();
+        assert_eq!(table.truncated_page(), None);
+        assert_eq!(table.num_rows, rows - 1);
+    }
+
+    #[test]
+    fn test_reserve_preallocates_pages() {
+        let mut table = Table::with_config(TableConfig { max_pages: 400, cache_capacity: 400, ..TableConfig::default() });
+        let pages = 5000usize.div_ceil(ROWS_PER_PAGE);
+
+        table.reserve(5000);
+        assert_eq!(table.allocated_pages(), pages);
+        assert!((0..pages).all(|page_num| table.is_page_resident(page_num)));
+        assert!(!table.is_page_resident(pages));
+
+        for i in 0..5000u64 {
+            assert!(matches!(
+                execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i))),
+                ExecuteResult::Inserted { .. }
+            ));
+        }
+        assert_eq!(table.allocated_pages(), pages);
+
+        // more than the table can hold reserves only what it can hold
+        let mut table = Table::new();
+        table.reserve(usize::MAX);
+        assert_eq!(table.allocated_pages(), table.config().max_pages);
+    }
+
+    #[test]
+    fn test_delete_id_range() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 12\ndelete where id between 5 and 10\nselect id\ndelete where id between 10 and 5\ndelete where id between 0 and 100\nselect count\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 12 rows.
+rsql > 6 rows deleted
+rsql > 1
+2
+3
+4
+11
+12
+rsql > 0 rows deleted
+rsql > 6 rows deleted
+rsql > 0
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_import_jsonl() {
+        let mut table = Table::new();
+        let jsonl = concat!(
+            "{\"id\":1,\"username\":\"alice\",\"email\":\"alice@example.com\"}\n",
+            "\n",
+            "{ \"email\": null, \"username\": \"say \\\"hi\\\" \\u00e9\", \"id\": 2 }\n",
+            "{\"id\":3,\"username\":null,\"email\":\"\"}\n",
+        );
+
+        let count = import_jsonl(&mut table, &mut Cursor::new(jsonl)).unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(table.get(0), Some(Row::new(1, "alice".to_string(), "alice@example.com".to_string())));
+        assert_eq!(table.get(1), Some(Row::try_new_nullable(2, Some("say \"hi\" é".to_string()), None).unwrap()));
+        assert_eq!(table.get(2), Some(Row::try_new_nullable(3, None, Some(String::new())).unwrap()));
+    }
+
+    #[test]
+    fn test_import_jsonl_reports_line_of_missing_field() {
+        let mut table = Table::new();
+        let jsonl = "{\"id\":1,\"username\":\"a\",\"email\":\"a@example.com\"}\n{\"id\":2,\"username\":\"b\"}\n";
+
+        let result = import_jsonl(&mut table, &mut Cursor::new(jsonl));
+
+        match result {
+            Err(err @ ImportError::Malformed { line: 2, .. }) => {
+                assert_eq!(err.to_string(), "line 2: missing field 'email'");
+            }
+            other => panic!("expected Malformed on line 2, got {:?}", other),
+        }
+        assert_eq!(table.num_rows, 1);
+
+        let err = import_jsonl(&mut table, &mut Cursor::new("{\"id\":2,\"username\":\"b\",\"email\":true}\n")).unwrap_err();
+        assert_eq!(err.to_string(), "line 1: unsupported value 'true'");
+    }
+
+    #[test]
+    fn test_select_count_with_filter() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 150\nselect count where id between 1 and 100\nselect count where id = 42\nselect count where id between 200 and 300\nselect count where username = user7\nselect count\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 150 rows.
+rsql > 100
+rsql > 1
+rsql > 0
+rsql > 1
+rsql > 150
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_raw_shows_padding() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert 1 \"bo b\" b@x.io\n.raw on\nselect id, username\n.mode csv\nselect email\n.raw off\nselect username\n.raw maybe\n.exit\n",
+        );
+
+        let username = format!("bo\\x20b{}", "\\x00".repeat(28));
+        let email = format!("b@x.io{}", "\\x00".repeat(249));
+        let expected = format!(
+            "rsql > 1 row inserted\nrsql > rsql > 1 {}\nrsql > rsql > {}\nrsql > rsql > bo b\nrsql > Usage: .raw on|off\nrsql > ",
+            username, email
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_validate_reports_duplicate_ids() {
+        let mut table = Table::new();
+        execute_insert(&mut table, &Row::new(1, "a".to_string(), "a@x.io".to_string()));
+        execute_insert(&mut table, &Row::new(2, "b".to_string(), "b@x.io".to_string()));
+        assert_eq!(table.validate(), Ok(()));
+
+        // written straight into the slots, past insert's checks
+        serialize(&Row::new(1, "c".to_string(), "c@x.io".to_string()), table.row_slot(2));
+        let mut bad_utf8 = Row::new(99, "d".to_string(), "d@x.io".to_string());
+        bad_utf8.username[1] = 0xff;
+        serialize(&bad_utf8, table.row_slot(3));
+        table.num_rows = 4;
+
+        assert_eq!(
+            table.validate(),
+            Err(vec![
+                IntegrityError::DuplicateId { id: 1, first: 0, row: 2 },
+                IntegrityError::IdNotBelowNextId { row: 3, id: 99, next_id: 3 },
+                IntegrityError::InvalidUtf8 { row: 3, column: Column::Username },
+            ])
+        );
+        let output = run_script(&mut table, ".check\n.exit\n");
+        let expected = "rsql > row 2: duplicate id 1 (also at row 0)
+row 3: id 99 is not below the next id 3
+row 3: username is not valid UTF-8
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repl_separator() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert 1 alice a@x.io\n.separator tab\nselect\n.separator |\nselect id, email\n.separator\n.separator space\nselect\n.exit\n",
+        );
+
+        let expected = "rsql > 1 row inserted
+rsql > rsql > 1\talice\ta@x.io
+rsql > rsql > 1|a@x.io
+rsql > Usage: .separator <text>|tab|space|comma|pipe
+rsql > rsql > 1 alice a@x.io
+rsql > ";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_column_limits() {
+        let limits = Table::column_limits();
+        assert_eq!(limits, ColumnLimits { username: COLUMN_USERNAME_SIZE, email: COLUMN_EMAIL_SIZE });
+
+        let mut table = Table::new();
+        let at_limit = Row::try_new(1, "u".repeat(limits.username), "e".repeat(limits.email)).unwrap();
+        assert!(matches!(execute_insert(&mut table, &at_limit), ExecuteResult::Inserted { id: 1 }));
+        assert!(Row::try_new(2, "u".repeat(limits.username + 1), String::new()).is_err());
+        assert!(Row::try_new(2, String::new(), "e".repeat(limits.email + 1)).is_err());
+    }
+
+    #[test]
+    fn test_select_first_and_last() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            ".seed 5\nselect first\nselect last\ndelete where id = 5\nselect last\nselect first where id between 3 and 4\n.mode csv\nselect first\n.exit\n",
+        );
+
+        let expected = "rsql > Seeded 5 rows.
+rsql > 1 user1 user1@example.com
+rsql > 5 user5 user5@example.com
+rsql > 1 row deleted
+rsql > 4 user4 user4@example.com
+rsql > 3 user3 user3@example.com
+rsql > rsql > 1,user1,user1@example.com
+rsql > ";
+        assert_eq!(output, expected);
+        assert!(matches!(
+            parse("select last").unwrap().statement_type(),
+            StatementType::Select(select) if select.row_end == Some(RowEnd::Last)
+        ));
+    }
+
+    #[test]
+    fn test_select_first_and_last_on_empty_table() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "select first\nselect last\n.exit\n");
+
+        assert_eq!(output, "rsql > No rows.\nrsql > No rows.\nrsql > ");
+    }
+
+    #[test]
+    fn test_save_over_open_file() {
+        let path = three_page_file("save_over_open");
+        let rows = 2 * ROWS_PER_PAGE + 1;
+        let mut table = Table::open(&path).unwrap();
+
+        run_script(&mut table, &format!(".save {}\n.exit\n", path));
+        execute_insert(&mut table, &Row::new(1000, "late".to_string(), "late@example.com".to_string()));
+        table.flush_dirty().unwrap();
+        drop(table);
+
+        let table = Table::open(&path).unwrap();
+        assert_eq!(table.num_rows, rows + 1);
+        assert_eq!(table.get(0), Some(Row::new(0, "user0".to_string(), "user0@example.com".to_string())));
+        assert_eq!(table.get(rows).map(|row| row.id), Some(1000));
+    }
+
+    #[test]
+    fn test_uncommitted_transaction_is_not_durable() {
+        let path = temp_db_path("uncommitted_txn");
+        {
+            let mut table = Table::open(&path).unwrap();
+            run_script(&mut table, "insert 1 a a@example.com\nbegin\ninsert 2 b b@example.com\n.exit\n");
+            // the exit path flushes; an open transaction must not reach the file
+            table.flush_dirty().unwrap();
+        }
+        let mut reopened = Table::open(&path).unwrap();
+        let output = run_script(&mut reopened, "select\n.exit\n");
+        assert_eq!(output, "rsql > 1 a a@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_wal_replay_keeps_only_committed_transactions() {
+        let path = temp_db_path("wal_txn_markers");
+        {
+            let mut table = Table::open(&path).unwrap();
+            table.begin_transaction().unwrap();
+            table.insert_row(Row::new(1, "a".to_string(), "a@example.com".to_string())).unwrap();
+            table.rollback_transaction().unwrap();
+            table.insert_row(Row::new(2, "b".to_string(), "b@example.com".to_string())).unwrap();
+            table.begin_transaction().unwrap();
+            table.insert_row(Row::new(3, "c".to_string(), "c@example.com".to_string())).unwrap();
+            // dropped without committing, as if the process crashed
+        }
+        let mut reopened = Table::open(&path).unwrap();
+        let output = run_script(&mut reopened, "select\n.exit\n");
+        assert_eq!(output, "rsql > 2 b b@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_commit_makes_transaction_durable() {
+        let path = temp_db_path("committed_txn");
+        {
+            let mut table = Table::open(&path).unwrap();
+            run_script(&mut table, "begin\ninsert 1 a a@example.com\ncommit\n.exit\n");
+        }
+        assert_eq!(std::fs::metadata(wal_path(&path)).unwrap().len(), 0);
+        let mut reopened = Table::open(&path).unwrap();
+        let output = run_script(&mut reopened, "select\n.exit\n");
+        assert_eq!(output, "rsql > 1 a a@example.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_select_aggregates_apply_filter() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 4 a a@example.com\ninsert 1 b b@example.com\ninsert 2 a c@example.com\n.exit\n");
+
+        let output = run_script(
+            &mut table,
+            "select min where username = a\nselect max where id between 1 and 3\nselect avg where email like c%\nselect max where username = z\n.exit\n",
+        );
+
+        assert_eq!(output, "rsql > 2\nrsql > 2\nrsql > 2.00\nrsql > No rows.\nrsql > ");
+    }
+
+    #[test]
+    fn test_seed_stops_at_largest_id() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "insert 18446744073709551615 a a@b.com\n.seed 1\n.exit\n");
+
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > Error: Duplicate key.\nSeeded 0 of 1 rows.\nrsql > ");
+        assert_eq!(table.num_rows, 1);
+    }
+
+    #[test]
+    fn test_validate_accepts_largest_id() {
+        let mut table = Table::new();
+        run_script(&mut table, "insert 18446744073709551615 a a@b.com\n.exit\n");
+
+        assert_eq!(table.validate(), Ok(()));
+        let output = run_script(&mut table, ".check\n.exit\n");
+        assert_eq!(output, "rsql > ok\nrsql > ");
+    }
+
+    #[test]
+    fn test_repl_reopen_current_file() {
+        let path = temp_db_path("reopen_current");
+        let mut table = Table::open(&path).unwrap();
+
+        let output = run_script(&mut table, &format!("insert 1 a a@example.com\n.open {}\nselect\n.exit\n", path));
+
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > rsql > 1 a a@example.com\nrsql > ");
+        assert!(matches!(Table::open(&path), Err(OpenError::Locked(_))));
+    }
+
+    #[test]
+    fn test_repl_failed_open_keeps_current_table() {
+        let path = temp_db_path("open_fails");
+        let mut table = Table::open(&path).unwrap();
+        let directory = std::env::temp_dir().display().to_string();
+
+        let output = run_script(&mut table, &format!("insert 1 a a@example.com\n.open {}\nselect\n.exit\n", directory));
+
+        assert!(output.contains(&format!("Error opening '{}'", directory)));
+        assert!(output.ends_with("rsql > 1 a a@example.com\nrsql > "));
+        // the current table holds its lock again
+        assert!(matches!(Table::open(&path), Err(OpenError::Locked(_))));
+    }
+
+    #[test]
+    fn test_read_refuses_runaway_nesting() {
+        let script = temp_db_path("read_self");
+        std::fs::write(&script, format!("insert 1 a a@example.com\n.read {}\n", script)).unwrap();
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, &format!(".read {}\nselect count\n.exit\n", script));
+
+        // each nested read after the first hits the duplicate id
+        let mut expected = "rsql > 1 row inserted\n".to_string();
+        expected.push_str(&"line 1: Error: Duplicate key.\n".repeat(15));
+        expected.push_str(&format!("Error reading '{}': scripts nested more than 16 deep\nrsql > 1\nrsql > ", script));
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_scan_limit_stops_reading_pages() {
+        let path = three_page_file("scan_limit_pages");
+        let mut table = Table::open(&path).unwrap();
+
+        let output = run_script(&mut table, ".scanlimit 2\nselect id\n.exit\n");
+
+        assert_eq!(output, "rsql > rsql > 0\n1\nscan limit reached after 2 rows\nrsql > ");
+        assert_eq!(table.page_loads(), 1);
+    }
+
+    #[test]
+    fn test_insert_tuples_with_quoted_delimiters() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert (1,\"Doe, John\",x@y.com)\ninsert (2,\"a)b\",x@y.com), (3, \"c\\\"),(\", z@y.com)\nselect\n.exit\n",
+        );
+
+        assert_eq!(
+            output,
+            "rsql > 1 row inserted\nrsql > 2 rows inserted\nrsql > 1 Doe, John x@y.com\n2 a)b x@y.com\n3 c\"),( z@y.com\nrsql > "
+        );
+    }
+
+    #[test]
+    fn test_update_quoted_value_with_comma() {
+        let mut table = Table::new();
+
+        let output = run_script(
+            &mut table,
+            "insert 1 a a@b.com\nupdate 1 set username=\"Doe, John\", email=\"x,y@b.com\"\nselect\n.exit\n",
+        );
+
+        assert_eq!(output, "rsql > 1 row inserted\nrsql > 1 row updated\nrsql > 1 Doe, John x,y@b.com\nrsql > ");
+    }
+
+    #[test]
+    fn test_select_first_and_last_reject_order_and_limit() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, ".seed 2\nselect first order by id desc\nselect LAST limit 0\n.exit\n");
+
+        assert!(output.contains("'first' cannot be combined with 'order by'"));
+        assert!(output.contains("'last' cannot be combined with 'limit'"));
+        assert!(parse("select first where id = 1 limit 1").is_err());
+    }
+
+    #[test]
+    fn test_delete_keyword_must_be_whole_word() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, ".seed 2\ndeleted\nDELETE where id = 1\nselect id\n.exit\n");
+
+        assert_eq!(
+            output,
+            "rsql > Seeded 2 rows.\nrsql > Unrecognized keyword at start of 'deleted'\nrsql > 1 row deleted\nrsql > 2\nrsql > "
+        );
+    }
+
+    #[test]
+    fn test_transaction_keywords_must_be_whole_words() {
+        let mut table = Table::new();
+
+        let output = run_script(&mut table, "beginning\nbegin now\nBEGIN\ncommitted\nrollbackk\nrollback\n.exit\n");
+
+        let expected = "rsql > Unrecognized keyword at start of 'beginning'
+rsql > Syntax Error in 'begin now'
+rsql > rsql > Unrecognized keyword at start of 'committed'
+rsql > Unrecognized keyword at start of 'rollbackk'
+rsql > rsql > ";
+        assert_eq!(output, expected);
+        assert!(!table.in_transaction());
+    }
+
+    /// A saved file of `ROWS_PER_PAGE + 1` rows whose second page fails its
+    /// checksum.
+    fn corrupt_second_page(name: &str) -> String {
+        let path = temp_db_path(name);
+        let mut table = Table::new();
+        for i in 0..(ROWS_PER_PAGE + 1) as u64 {
+            execute_insert(&mut table, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+        }
+        table.save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[page_offset(1) as usize + 10] ^= 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_repl_reports_corrupt_page() {
+        let path = corrupt_second_page("repl_corrupt");
+        let mut table = Table::open(&path).unwrap();
+
+        let output = run_script(
+            &mut table,
+            "select\nselect count\nselect max\ninsert 99 x x@example.com\ninsert or replace 1 y y@example.com\nupdate 1 set username=z\ndelete where id = 1\ndelete\n.dump\n.check\n.vacuum\n.exit\n",
+        );
+
+        let error = "Error: checksum mismatch on page 1";
+        // select prints the readable first page before stopping
+        assert!(output.contains(&format!("user{0} user{0}@example.com\n{1}", ROWS_PER_PAGE - 1, error)));
+        // rows on the readable page can still be upserted and updated
+        assert!(output.contains("1 row inserted\nrsql > 1 row updated\n"));
+        assert_eq!(output.matches(error).count(), 7);
+        assert!(output.contains("Error dumping table: checksum mismatch on page 1"));
+        assert!(output.contains("rsql > page 1: checksum mismatch on page 1\n"));
+        assert_eq!(table.num_rows, ROWS_PER_PAGE + 1);
+        drop(table);
+
+        let reopened = Table::open(&path).unwrap();
+        assert!(matches!(reopened.try_read_slot(1), Ok(slot) if deserialize(&slot).id == 1));
+    }
+
+    #[test]
+    fn test_wal_replay_into_corrupt_page_fails_open() {
+        let path = corrupt_second_page("wal_corrupt");
+        let logged = temp_db_path("wal_corrupt_log");
+        {
+            let mut source = Table::open(&logged).unwrap();
+            for i in 0..(ROWS_PER_PAGE + 1) as u64 {
+                execute_insert(&mut source, &Row::new(i, format!("user{}", i), format!("user{}@example.com", i)));
+            }
+            // dropped without flush_dirty, so every insert stays in its log
+        }
+        // replaying the log writes a row into the corrupt second page
+        std::fs::copy(wal_path(&logged), wal_path(&path)).unwrap();
+
+        assert!(matches!(Table::open(&path), Err(OpenError::Io(err)) if err.kind() == std::io::ErrorKind::InvalidData));
     }
 }